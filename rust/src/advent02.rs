@@ -1,325 +1,746 @@
-use std::cmp::Ordering;
+use std::collections::BTreeMap;
 use std::io::{self};
-use std::io::{BufRead, BufReader};
+use std::io::{BufRead, BufReader, Write};
 use std::fs::{exists, File};
 use std::iter::zip;
-use std::collections::HashMap;
+
+// reports are read and evaluated in batches, so the whole input is never kept in memory
+const BATCH_SIZE: usize = 4096;
+
+fn open_input(file_path: &str) -> Result<BufReader<File>, io::Error>
+{
+    if exists(file_path).is_err()
+    {
+        panic!("File not found: {file_path}");
+    }
+
+    // otaznicek prakticky robi unwrap, odbaluje
+    let file = File::open(file_path)?;
+    Ok(BufReader::new(file))
+}
 
 struct Input
 {
-    lines: Vec<String>,
     parsed: Vec<Vec<i32>>,
 }
 
 fn get_input(file_path: &str) -> Result<Input, io::Error>
+// whole input at once, statistics need to see all reports anyway
 {
-    if exists(file_path).is_err()
+    let parsed = open_input(file_path)?.lines()
+        .filter(|line| line.as_ref().map_or(true, |line| !line.trim().is_empty()))
+        .map(|line| parse_line(&line?))
+        .collect::<Result<Vec<Vec<i32>>, io::Error>>()?;
+
+    Ok(Input { parsed })
+}
+
+fn parse_line(line: &str) -> Result<Vec<i32>, io::Error>
+{
+    line.split_whitespace()
+        .map(|x| x.parse::<i32>().map_err(|e| io::Error::new(io::ErrorKind::InvalidData,
+            format!("Error while parsing \"{x}\" on line \"{line}\": {e}"))))
+        .collect()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Direction
+{
+    Increasing,
+    Decreasing,
+    Either,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Slope
+{
+    Rising,
+    Falling,
+    Flat,
+}
+
+#[derive(Clone, Debug)]
+struct SafetyPolicy
+{
+    // allowed absolute difference between two adjacent levels, plateaus not included
+    min_step: u32,
+    max_step: u32,
+    direction: Direction,
+    // equal adjacent levels, they don't count as a direction change
+    allow_plateaus: bool,
+    // how many times can report switch between rising and falling
+    max_direction_changes: usize,
+}
+
+impl Default for SafetyPolicy
+{
+    // rules from the puzzle
+    fn default() -> Self
     {
-        panic!("File not found: {file_path}");
+        SafetyPolicy {
+            min_step: 1,
+            max_step: 3,
+            direction: Direction::Either,
+            allow_plateaus: false,
+            max_direction_changes: 0,
+        }
     }
+}
 
-    // otaznicek prakticky robi unwrap, odbaluje
-    let file = File::open(file_path)?;
-    let reader = BufReader::new(file);
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Violation
+{
+    StepTooSmall,
+    StepTooLarge,
+    WrongDirection,
+    DirectionChange,
+    Plateau,
+}
 
-    let lines: Vec<String> = reader.lines().map(|x|x.unwrap()).collect();
-    let parsed: Vec<Vec<i32>> = lines.clone().iter().map(parse_line).collect();
-    return Ok(Input { lines: lines, parsed: parsed });
+impl std::fmt::Display for Violation
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+    {
+        let reason = match self
+        {
+            Violation::StepTooSmall => "step too small",
+            Violation::StepTooLarge => "step too large",
+            Violation::WrongDirection => "wrong direction",
+            Violation::DirectionChange => "direction change",
+            Violation::Plateau => "plateau",
+        };
+        write!(f, "{reason}")
+    }
 }
 
-fn parse_line(line: &String) -> Vec<i32>
+impl SafetyPolicy
 {
-    return line.split_whitespace()
-        .map(|x| match x.parse::<i32>()
+    fn slope(&self, from: i32, to: i32) -> Result<Slope, Violation>
+    // error if step between two adjacent levels is not allowed at all
+    {
+        let slope = match to.cmp(&from)
+        {
+            std::cmp::Ordering::Greater => Slope::Rising,
+            std::cmp::Ordering::Less => Slope::Falling,
+            std::cmp::Ordering::Equal =>
             {
-                Ok(n) => n,
-                Err(e) => panic!("Error while parsing \"{c}\": {e}", c=x)
-            }).collect();
+                return if self.allow_plateaus { Ok(Slope::Flat) } else { Err(Violation::Plateau) };
+            }
+        };
+
+        let step = from.abs_diff(to);
+
+        if matches!((self.direction, slope),
+            (Direction::Increasing, Slope::Falling) | (Direction::Decreasing, Slope::Rising))
+        {
+            Err(Violation::WrongDirection)
+        }
+        else if step < self.min_step
+        {
+            Err(Violation::StepTooSmall)
+        }
+        else if step > self.max_step
+        {
+            Err(Violation::StepTooLarge)
+        }
+        else
+        {
+            Ok(slope)
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct RemovedLevel
+{
+    index: usize,
+    value: i32,
 }
 
-fn levels_safe(levels: &Vec<i32>) -> bool
+#[derive(Clone, Debug, PartialEq)]
+enum Verdict
 {
-    // TODO: why &? why do I need to borrow when slicing?
-    // TODO: why does this needs to be a mutable if all() is not changing it?
-    let diff = zip(&levels[0..levels.len()-1],&levels[1..])
-    //.map(|(x, y): (&i32, &i32)| x - y > 0)
-        .map(|(x, y): (&i32, &i32)| match x.cmp(y)
+    Safe,
+    // safe, but only after the Problem Dampener removed some levels
+    Dampened(Vec<RemovedLevel>),
+    // index points to the first level of the first offending pair
+    Unsafe { index: usize, pair: (i32, i32), reason: Violation },
+}
+
+impl std::fmt::Display for Verdict
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+    {
+        match self
+        {
+            Verdict::Safe => write!(f, "safe"),
+            Verdict::Dampened(removed) =>
             {
-                // dereferencing a borrow??
-                Ordering::Greater => match x.abs_diff(*y) 
-                    {
-                        1 ..= 3 => Ordering::Greater,
-                        _ => Ordering::Equal,
-                    },
-                Ordering::Less => match x.abs_diff(*y) 
-                    {
-                        1 ..= 3 => Ordering::Less,
-                        _ => Ordering::Equal,
-                    },
-                Ordering::Equal => Ordering::Equal
-            }
-    ).collect::<Vec<Ordering>>();
-    // TODO: optimize to pass vector only once with Optional<bool> (for 3 values)
-    return diff.iter().all(|x: &Ordering| x == &Ordering::Less) 
-        || diff.iter().all(|x: &Ordering| x == &Ordering::Greater);
+                write!(f, "safe after removing level")?;
+
+                for (idx, level) in removed.iter().enumerate()
+                {
+                    let separator = if idx == 0 { " " } else { ", " };
+                    write!(f, "{separator}{} (value {})", level.index, level.value)?;
+                }
+                Ok(())
+            },
+            Verdict::Unsafe { index, pair, reason } =>
+                write!(f, "unsafe, {reason} between levels {index} and {} ({} -> {})", index + 1, pair.0, pair.1),
+        }
+    }
 }
 
-fn signum(n: i32) -> i32
+fn first_violation(levels: &[i32], policy: &SafetyPolicy) -> Option<(usize, Violation)>
+// index of the first level in the pair that breaks the policy
 {
-    match n
+    let mut last_slope: Option<Slope> = None;
+    let mut changes = 0;
+
+    for (idx, pair) in levels.windows(2).enumerate()
     {
-        x if x < 0 => -1,
-        x if x > 0 => 1,
-        _ => 0,
+        let slope = match policy.slope(pair[0], pair[1])
+        {
+            Ok(slope) => slope,
+            Err(violation) => return Some((idx, violation)),
+        };
+
+        match (last_slope, slope)
+        {
+            (_, Slope::Flat) => (),
+            (Some(last), _) if last != slope =>
+            {
+                changes += 1;
+
+                if changes > policy.max_direction_changes
+                {
+                    return Some((idx, Violation::DirectionChange));
+                }
+                last_slope = Some(slope);
+            },
+            _ => last_slope = Some(slope),
+        }
     }
+
+    None
 }
 
-fn levels_safe_bonus2(levels: &Vec<i32>) -> bool
+#[derive(Clone, Copy)]
+struct DampenerState
 {
-    // compute 2nd order derivations and detect where it changes
-    // not the memory optimal solution, but I thought it would be interesting
+    // fewest removed levels to reach this state
+    removed: usize,
+    // previous kept level and its state
+    prev: Option<(usize, usize)>,
+}
 
-    let diff: Vec<i32> = zip(&levels[0..levels.len()-1],&levels[1..])
-        .map(|(x, y): (&i32, &i32)| signum(x - y)).collect();
-    let diff_2nd = zip(&diff[0..diff.len()-1],&diff[1..])
-        .map(|(x, y): (&i32, &i32)| signum(x - y)).collect::<Vec<i32>>();
+fn dampen(levels: &[i32], k: usize, policy: &SafetyPolicy) -> Option<Vec<usize>>
+// fewest levels that have to be removed so that the rest is safe under given policy,
+// None if more than k levels would have to be removed
+{
+    let n = levels.len();
 
-    // can't fold it like this, I need mutable hashmap defined beforehand
-    // let (_, indices): (i32, HashMap<i32, Vec<i32>>) = diff_2n
-    //     .fold((0, HashMap::new()), |(idx, map), x| 
-    //         {
-    //             map.entry(x)
-    //             .and_modify(|v: &mut Vec<i32>| v.push(idx))
-    //             .or_insert(vec![idx]);
-    //             return (idx + 1, map)
-    //         }
-    //         );
+    if n == 0
+    {
+        return Some(vec![]);
+    }
 
-    let mut indices: HashMap<i32, Vec<i32>> = HashMap::new();
-    
-    let _ = diff_2nd.iter()
-        .enumerate()
-        .map(|(idx, value): (usize, &i32)|
-            match value
+    // more changes than steps can't happen, keeps the state table small
+    let max_changes = policy.max_direction_changes.min(n);
+
+    // state of a kept level: last non-flat slope (none, rising, falling) and direction changes so far
+    let state_cnt = 3 * (max_changes + 1);
+    let state_idx = |slope: Option<Slope>, changes: usize| -> usize
+    {
+        let slope_idx = match slope
+        {
+            None | Some(Slope::Flat) => 0,
+            Some(Slope::Rising) => 1,
+            Some(Slope::Falling) => 2,
+        };
+        slope_idx * (max_changes + 1) + changes
+    };
+    let slope_of = |idx: usize| -> Option<Slope>
+    {
+        match idx / (max_changes + 1)
         {
-            -1 | 1 => { 
-                // TODO: why deref?
-                indices.entry(*value)
-                    .and_modify(|v: &mut Vec<i32>| v.push(idx as i32))
-                    .or_insert(vec![idx as i32]); }, 
-            _ => (),
-        }).collect::<()>();
+            0 => None,
+            1 => Some(Slope::Rising),
+            _ => Some(Slope::Falling),
+        }
+    };
+
+    // removals[i][state] - fewest removed levels for a valid report ending with (kept) levels[i]
+    // and previous kept level with its state, so the removed levels can be found afterwards
+    let mut removals: Vec<Vec<DampenerState>> = Vec::with_capacity(n);
+    // (removed levels, last kept level, its state)
+    let mut fewest_total: Option<(usize, usize, usize)> = None;
 
-    match (indices.get(&-1), indices.get(&1))
+    for idx in 0..n
     {
-        // ascending or descending only, check ranges 1-3
-        (None, None) => { return levels_safe(levels)},
+        let mut fewest = vec![DampenerState { removed: usize::MAX, prev: None }; state_cnt];
+        // worst case, drop everything before current level
+        fewest[state_idx(None, 0)] = DampenerState { removed: idx, prev: None };
 
-        // one change only, but not saying how many elements are equal/
-        (None, Some(v)) | (Some(v), None) => 
+        // previous kept level can't be further than k + 1 positions,
+        // otherwise more than k levels between them would be removed
+        for prev in idx.saturating_sub(k + 1)..idx
         {
-            // figure out whether index subtraction is correct
-            // -1 to point to the last element, -2 because 2nd diff array is shorter
-            if v.len() == 1 && (v[0] == 0 || v[0] == levels.len() as i32 - 1 - 2)
+            let step = match policy.slope(levels[prev], levels[idx])
             {
-                // in case of difference in 0 index, we don't know which number to omit, try all 3
-                // otherwise, omit last checked element, idx + 2
-                if v[0] == 0
+                Ok(step) => step,
+                Err(_) => continue,
+            };
+
+            for (state, reached) in removals[prev].iter().enumerate()
+            {
+                if reached.removed == usize::MAX
                 {
-                    let (mut v1, mut v2, mut v3) = (levels.clone(), levels.clone(), levels.clone());
-                    v1.remove(0);
-                    v2.remove(1);
-                    v3.remove(2);
-
-                    // when change is discovered on first index, we don't know
-                    // which of first three numbers is problematic, so try them all
-                    return levels_safe(&v1) || levels_safe(&v2) || levels_safe(&v3);
+                    continue;
                 }
 
-                let mut copy = levels.clone();
-                copy.remove((v[0] + 2) as usize);
-                return levels_safe(&copy);
+                let (last, changes) = (slope_of(state), state % (max_changes + 1));
+                let next = match (last, step)
+                {
+                    (_, Slope::Flat) => state,
+                    (None, _) => state_idx(Some(step), changes),
+                    (Some(last), _) if last == step => state,
+                    _ if changes < max_changes => state_idx(Some(step), changes + 1),
+                    _ => continue,
+                };
+
+                let removed = reached.removed + (idx - prev - 1);
+
+                // on ties prefer closer predecessor, so earlier levels get removed as in the puzzle
+                if removed <= fewest[next].removed
+                {
+                    fewest[next] = DampenerState { removed, prev: Some((prev, state)) };
+                }
             }
-            return false;
-        },
+        }
 
-        (Some(v1), Some(v2)) =>
+        // everything after current level is dropped
+        for (state, reached) in fewest.iter().enumerate()
         {
-            if v1.len() == 1 && v2.len() == 1
-            { 
-                let mut idx = if v1.get(0) < v2.get(0) 
-                { *v1.get(0).unwrap() } 
-                else { *v2.get(0).unwrap() };
-
-                // if first idx is problematic, we don't know which of 3 numbers should be removed
-                if idx == 0
-                {
-                    let (mut v1, mut v2, mut v3) = (levels.clone(), levels.clone(), levels.clone());
-                    v1.remove(0);
-                    v2.remove(1);
-                    v3.remove(2);
+            let removed = reached.removed.saturating_add(n - 1 - idx);
 
-                    // when change is discovered on first index, we don't know
-                    // which of first three numbers is problematic, so try them all
-                    return levels_safe(&v1) || levels_safe(&v2) || levels_safe(&v3);
+            if fewest_total.is_none_or(|(best, _, _)| removed <= best)
+            {
+                fewest_total = Some((removed, idx, state));
+            }
+        }
+        removals.push(fewest);
+    }
 
-                }
-                idx += 2; // increase idx by two to remove the last element
+    let (removed_cnt, mut idx, mut state) = fewest_total.filter(|(removed, _, _)| *removed <= k)?;
+
+    // walk back through kept levels, everything in between was removed
+    let mut kept = vec![false; n];
+    loop
+    {
+        kept[idx] = true;
+
+        match removals[idx][state].prev
+        {
+            Some((prev, prev_state)) => { idx = prev; state = prev_state; },
+            None => break,
+        }
+    }
 
-                // why dereference?
-                let mut shortened = levels.clone();
-                shortened.remove((idx) as usize);
-                return levels_safe(&shortened);
+    let removed: Vec<usize> = (0..n).filter(|&i| !kept[i]).collect();
+    debug_assert_eq!(removed.len(), removed_cnt);
+    Some(removed)
+}
+
+fn dampen_brute_force(levels: &[i32], k: usize, policy: &SafetyPolicy) -> Option<Vec<usize>>
+// try removing every combination of up to k levels, fewest removed first
+// exponential in k, only meant as an oracle for the dynamic programming version
+{
+    let n = levels.len();
+    let mut remaining: Vec<i32> = Vec::with_capacity(n);
+
+    for removed_cnt in 0..=k.min(n)
+    {
+        // indices of removed levels, start with the lexicographically first combination
+        let mut removed: Vec<usize> = (0..removed_cnt).collect();
+
+        loop
+        {
+            remaining.clear();
+            remaining.extend(levels.iter().enumerate()
+                .filter(|(idx, _)| !removed.contains(idx))
+                .map(|(_, level)| *level));
+
+            if first_violation(&remaining, policy).is_none()
+            {
+                return Some(removed);
+            }
+
+            // next combination - find rightmost index that can still be moved right
+            let movable = (0..removed_cnt).rev().find(|&i| removed[i] < n - removed_cnt + i);
+
+            match movable
+            {
+                Some(i) =>
+                {
+                    removed[i] += 1;
+                    for j in i + 1..removed_cnt
+                    {
+                        removed[j] = removed[j - 1] + 1;
+                    }
+                },
+                None => break,
             }
-            return false; // more than one rising or falling index, fail
         }
-        _ => { return levels_safe(levels); }
     }
 
-    // first two elements determine slope (rising/falling), third is checked
-    // what if first or second is wrong?
+    None
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Algorithm
+{
+    // O(n * k * (max_direction_changes + 1)) state transitions, see dampen
+    Dynamic,
+    // O(n^(k + 1)), every combination of removed levels
+    BruteForce,
+}
+
+fn check_report(levels: &[i32], k: usize, policy: &SafetyPolicy, algorithm: Algorithm) -> Verdict
+// the way to check a report, everything else in this file goes through here
+{
+    let (index, reason) = match first_violation(levels, policy)
+    {
+        None => return Verdict::Safe,
+        Some(violation) => violation,
+    };
+
+    let removed = match algorithm
+    {
+        Algorithm::Dynamic => dampen(levels, k, policy),
+        Algorithm::BruteForce => dampen_brute_force(levels, k, policy),
+    };
+
+    match removed
+    {
+        Some(removed) => Verdict::Dampened(removed.into_iter()
+            .map(|index| RemovedLevel { index, value: levels[index] })
+            .collect()),
+        None => Verdict::Unsafe { index, pair: (levels[index], levels[index + 1]), reason },
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Mode
+{
+    // safe/dampened/unsafe counts and verdicts
+    Evaluate,
+    // statistical summary of all reports
+    Stats,
+}
 
-    // 0 1 2 3 3 2
-    // 1 1 1 0 -1
-    // 0 0 -1 -1
+struct Args
+{
+    mode: Mode,
+    input_path: String,
+    policy: SafetyPolicy,
+    // levels Problem Dampener can remove in part 2
+    tolerance: usize,
+    algorithm: Algorithm,
+    // optional file with verdict for every report
+    verdict_path: Option<String>,
+    // stats are computed for every tolerance up to this one
+    max_tolerance: usize,
+    json: bool,
+}
 
-    // 1 2 3 3 3
-    // 1 1 0 0 0
-    // 0 -1 0 0
+impl std::str::FromStr for Direction
+{
+    type Err = io::Error;
 
-    // 2 1 2 4  6 5 7
-    //-1 1 1 1 -1 1
-    // 1 0 0 -1  1
+    fn from_str(s: &str) -> Result<Self, Self::Err>
+    {
+        match s
+        {
+            "increasing" => Ok(Direction::Increasing),
+            "decreasing" => Ok(Direction::Decreasing),
+            "either" => Ok(Direction::Either),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidInput,
+                format!("Unknown direction \"{s}\", expected increasing, decreasing or either"))),
+        }
+    }
+}
 
-    // 1  2 2 3 4
-    // 1  0 1 1 
-    // -1 1 0 0
+impl std::str::FromStr for Algorithm
+{
+    type Err = io::Error;
 
+    fn from_str(s: &str) -> Result<Self, Self::Err>
+    {
+        match s
+        {
+            "dynamic" => Ok(Algorithm::Dynamic),
+            "brute-force" => Ok(Algorithm::BruteForce),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidInput,
+                format!("Unknown algorithm \"{s}\", expected dynamic or brute-force"))),
+        }
+    }
 }
 
-fn levels_safe_bonus(levels: &Vec<i32>) -> bool
+fn parse_args(args: impl Iterator<Item = String>) -> Result<Args, Box<dyn std::error::Error>>
 {
-    // desperate attempt to ditch the 2nd order derivation and use only
-    // 1st order to make it easier I guess? I know, not the best option
+    let mut parsed = Args {
+        mode: Mode::Evaluate,
+        input_path: r"D:\src\Advent2024\inputs\02.txt".to_string(),
+        policy: SafetyPolicy::default(),
+        tolerance: 1,
+        algorithm: Algorithm::Dynamic,
+        verdict_path: None,
+        max_tolerance: 3,
+        json: false,
+    };
 
-    let diff: Vec<i32> = zip(&levels[0..levels.len()-1],&levels[1..])
-        .map(|(x, y): (&i32, &i32)| signum(x - y)).collect();
+    let mut args = args;
 
-        
-    let mut indices: HashMap<i32, Vec<i32>> = HashMap::new();
-    
-    let _ = diff.iter()
-        .enumerate()
-        // TODO: maybe rewrite using foreach and force evalution
-        .map(|(idx, value): (usize, &i32)|
-            match value
+    while let Some(arg) = args.next()
+    {
+        // every option except flags takes a value
+        let mut value = || args.next().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput,
+            format!("Missing value for {arg}")));
+
+        match arg.as_str()
+        {
+            "--min-step" => parsed.policy.min_step = value()?.parse()?,
+            "--max-step" => parsed.policy.max_step = value()?.parse()?,
+            "--direction" => parsed.policy.direction = value()?.parse()?,
+            "--max-changes" => parsed.policy.max_direction_changes = value()?.parse()?,
+            "--tolerance" => parsed.tolerance = value()?.parse()?,
+            "--algorithm" => parsed.algorithm = value()?.parse()?,
+            "--verdicts" => parsed.verdict_path = Some(value()?),
+            "--max-tolerance" => parsed.max_tolerance = value()?.parse()?,
+            "--plateaus" => parsed.policy.allow_plateaus = true,
+            "--json" => parsed.json = true,
+            "--stats" => parsed.mode = Mode::Stats,
+            _ if arg.starts_with("--") => return Err(Box::new(io::Error::new(io::ErrorKind::InvalidInput,
+                format!("Unknown option {arg}")))),
+            _ => parsed.input_path = arg,
+        }
+    }
+
+    if parsed.policy.min_step > parsed.policy.max_step
+    {
+        return Err(Box::new(io::Error::new(io::ErrorKind::InvalidInput,
+            format!("Minimal step {} is larger than maximal step {}", parsed.policy.min_step, parsed.policy.max_step))));
+    }
+
+    Ok(parsed)
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct ReportCounts
+{
+    // safe without the Problem Dampener - part 1
+    safe_cnt: usize,
+    // safe after removing some levels, together with safe ones part 2
+    dampened_cnt: usize,
+    unsafe_cnt: usize,
+}
+
+impl ReportCounts
+{
+    fn add(&mut self, verdict: &Verdict)
+    {
+        match verdict
         {
-            _ => { 
-                // TODO: why deref?
-                indices.entry(*value)
-                    .and_modify(|v: &mut Vec<i32>| v.push(idx as i32))
-                    .or_insert(vec![idx as i32]); }, 
-        }).collect::<()>();
-
-    return match (indices.get(&-1), indices.get(&0), indices.get(&1))
-    {
-        // rising and falling only
-        (None, None, Some(_)) | (Some(_), None, None) => levels_safe(levels),
-        // (_, Some(v), _) if v.len() > 1 => false,
-        (Some(rising), _, Some(falling)) 
-            if rising.len() > 1 && falling.len() > 1 => false,
-        // equal max 1,
-        (Some(_), None, Some(short))
-        | (Some(short), None, Some(_))
-        | (Some(_), Some(short), None)
-        | (None, Some(short), Some(_))
-            if { short.len() == 1 } => 
+            Verdict::Safe => self.safe_cnt += 1,
+            Verdict::Dampened(_) => self.dampened_cnt += 1,
+            Verdict::Unsafe { .. } => self.unsafe_cnt += 1,
+        }
+    }
+}
+
+fn evaluate_reports(reader: impl BufRead, k: usize, policy: &SafetyPolicy, algorithm: Algorithm,
+    mut verdicts: Option<&mut dyn Write>) -> Result<ReportCounts, Box<dyn std::error::Error>>
+// reports within a batch are checked in parallel, verdicts are written in input order
+{
+    use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+
+    let mut counts = ReportCounts::default();
+    let mut lines = reader.lines();
+    let mut batch: Vec<String> = Vec::with_capacity(BATCH_SIZE);
+
+    loop
+    {
+        batch.clear();
+
+        for line in lines.by_ref().take(BATCH_SIZE)
+        {
+            let line = line?;
+
+            // empty lines (usually the last one) are not reports
+            if !line.trim().is_empty()
             {
-                // index with only different difference
-                let idx = short[0];
+                batch.push(line);
+            }
+        }
 
-                if idx == 0//diff.len() as i32 - 1
-                {
-                    // 5, 1,2,3 vs  2,1,3,4 vs 2, 5,3,4
-                    // -1,1,1   vs -1,1,1   vs 1,-1,1
+        if batch.is_empty()
+        {
+            break;
+        }
 
-                    // 2,4, 8,7 vs 2,4, 8,3
-                    // 1,1,-1   vs 1,1,-1
+        let evaluated = batch.par_iter()
+            .map(|line| parse_line(line).map(|levels| check_report(&levels, k, policy, algorithm)))
+            .collect::<Result<Vec<Verdict>, io::Error>>()?;
 
-                    // 2  4 1 5
-                    // 1 -1 1
+        for (line, verdict) in zip(&batch, &evaluated)
+        {
+            counts.add(verdict);
 
-                    // 7 10 8 10 11
-                    // 1 -1 1  1  1
+            if let Some(out) = verdicts.as_mut()
+            {
+                writeln!(out, "{line}: {verdict}")?;
+            }
+        }
+    }
 
-                    // 2  7 4 6
-                    // 1 -1 1
+    Ok(counts)
+}
 
-                    // if 2nd elem is slope is different from the rest
+#[derive(Debug, Default, PartialEq, serde::Serialize)]
+struct ReportStats
+{
+    report_cnt: usize,
+    // report length -> number of reports
+    lengths: BTreeMap<usize, usize>,
+    // absolute difference between adjacent levels -> number of steps
+    steps: BTreeMap<u32, usize>,
+    // switches between rising and falling (plateaus skipped) -> number of reports
+    direction_changes: BTreeMap<usize, usize>,
+    // reports safe when up to k levels can be removed, k is the index
+    safe_by_tolerance: Vec<usize>,
+}
 
-                    // is there an example where I have to remove 2nd but can't 1st?
+fn count_direction_changes(levels: &[i32]) -> usize
+{
+    let slopes: Vec<std::cmp::Ordering> = levels.windows(2)
+        .map(|pair| pair[1].cmp(&pair[0]))
+        .filter(|slope| slope.is_ne())
+        .collect();
 
-                    // not sure if to remove first or second element
+    slopes.windows(2).filter(|pair| pair[0] != pair[1]).count()
+}
 
-                }
+fn compute_stats(input: &Input, max_k: usize, policy: &SafetyPolicy, algorithm: Algorithm) -> ReportStats
+{
+    let mut stats = ReportStats { safe_by_tolerance: vec![0; max_k + 1], ..ReportStats::default() };
 
-                // either determine which element to delete or try both
-                let (mut v1, mut v2) = (levels.clone(), levels.clone());
-                v1.remove(idx as usize);
-                v2.remove(idx as usize + 1);
+    for levels in &input.parsed
+    {
+        stats.report_cnt += 1;
+        *stats.lengths.entry(levels.len()).or_default() += 1;
+        *stats.direction_changes.entry(count_direction_changes(levels)).or_default() += 1;
 
-                return levels_safe(&v1) || levels_safe(&v2);
-            },
-        _ => false,
-    };
+        for pair in levels.windows(2)
+        {
+            *stats.steps.entry(pair[0].abs_diff(pair[1])).or_default() += 1;
+        }
+
+        // fewest removed levels decide from which tolerance on the report is safe
+        let removed_cnt = match check_report(levels, max_k, policy, algorithm)
+        {
+            Verdict::Safe => Some(0),
+            Verdict::Dampened(removed) => Some(removed.len()),
+            Verdict::Unsafe { .. } => None,
+        };
+
+        if let Some(removed_cnt) = removed_cnt
+        {
+            stats.safe_by_tolerance[removed_cnt..].iter_mut().for_each(|cnt| *cnt += 1);
+        }
+    }
+
+    stats
+}
+
+fn print_stats_table(stats: &ReportStats)
+{
+    println!("Reports: {}", stats.report_cnt);
+
+    println!();
+    println!("{:>8} {:>8}", "Length", "Reports");
+    for (length, cnt) in &stats.lengths
+    {
+        println!("{length:>8} {cnt:>8}");
+    }
+
+    println!();
+    println!("{:>8} {:>8}", "Step", "Count");
+    for (step, cnt) in &stats.steps
+    {
+        println!("{step:>8} {cnt:>8}");
+    }
+
+    println!();
+    println!("{:>8} {:>8}", "Changes", "Reports");
+    for (changes, cnt) in &stats.direction_changes
+    {
+        println!("{changes:>8} {cnt:>8}");
+    }
+
+    println!();
+    println!("{:>8} {:>8} {:>8}", "k", "Safe", "New");
+    for (k, cnt) in stats.safe_by_tolerance.iter().enumerate()
+    {
+        let newly_safe = if k == 0 { *cnt } else { cnt - stats.safe_by_tolerance[k - 1] };
+        println!("{k:>8} {cnt:>8} {newly_safe:>8}");
+    }
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>>
 {
-    //println!("{arr:?}", arr=&v1[..v1.len()-1]);
-    // println!("Should be safe and is: {t}", t=levels_safe(&vec![1,2,3,4,5,6]));
-    // println!("Should be safe and is: {t}", t=levels_safe(&vec![4,3,2,1]));
-    // println!("Should be safe and is: {t}", t=levels_safe(&vec![8,9,10,11]));
-    // println!("Should be unsafe, is safe: {t}", t=levels_safe(&vec![4,6,2,3]));
-    // println!("Should be unsafe, is safe: {t}", t=levels_safe(&vec![1,1,1,1]));
-    // println!("Should be unsafe, is safe: {t}", t=levels_safe(&vec![8,6,7,3]));
-    // println!("Should be unsafe, is safe: {safe}", safe=levels_safe(&vec![11, 22, 22, 33]));
-    // println!("Should be unsafe, is safe: {safe}", safe=levels_safe(&vec![11, 9, 9, 8]));
-    // println!("Should be unsafe, is safe: {safe}", safe=levels_safe(&vec![2, 2, 1, 2, 2]));
-    // println!("Should be unsafe, is safe: {safe}", safe=levels_safe(&vec![2, 2, 3, 4, 4]));
-    // println!("Should be unsafe, is safe: {safe}", safe=levels_safe(&vec![7, 10, 8, 10, 11]));
-    // println!("Should be unsafe, is safe: {safe}", safe=levels_safe(&vec![29, 28, 27, 25, 26, 25, 22, 20]));
-    // println!("Should be unsafe, is safe: {safe}", safe=levels_safe(&vec![75, 77, 72, 70, 69]));
-
-    // bonus
-    // println!("Should be safe, is safe: {safe}", safe=levels_safe_bonus(&vec![1, 2, 3, 3, 4]));
-    // println!("Should be safe, is safe: {safe}", safe=levels_safe_bonus(&vec![2, 1, 2, 3, 4]));
-    // println!("Should be safe, is safe: {safe}", safe=levels_safe_bonus(&vec![1, 2, 3, 4, 3]));
-    // println!("Should be safe, is safe: {safe}", safe=levels_safe_bonus(&vec![8,6,4,4,1]));
-    // println!("Should be safe, is safe: {safe}", safe=levels_safe_bonus(&vec![2,1,4,5]));
-    // println!("Should be safe, is safe: {safe}", safe=levels_safe_bonus(&vec![7,5,3,3]));
-    // println!("Should be safe, is safe: {safe}", safe=levels_safe_bonus(&vec![7,10,8,10,11]));
-    // println!("Should be unsafe, is safe: {safe}", safe=levels_safe_bonus(&vec![1, 2, 3, 3, 3, 4]));
-    // println!("Should be unsafe, is safe: {safe}", safe=levels_safe_bonus(&vec![1, 2, 3, 2, 1]));
-    
-    // return Ok(());
+    let args = parse_args(std::env::args().skip(1))?;
 
-    let input: Input = get_input(r"D:\src\Advent2024\inputs\02.txt")?;
-    // let func = levels_safe;
-    let func = levels_safe_bonus;
+    if args.mode == Mode::Stats
+    {
+        let input = get_input(&args.input_path)?;
+        let stats = compute_stats(&input, args.max_tolerance, &args.policy, args.algorithm);
 
-    let _printed = zip(&input.lines, &input.parsed)
-        .map(|(line, vec)| format!("{}: {}", line, func(&vec)))
-        .fold("".to_owned(), |x, y| x + ": " + &y + "\n");
-    println!("{_printed}");
+        match args.json
+        {
+            true => println!("{}", serde_json::to_string_pretty(&stats)?),
+            false => print_stats_table(&stats),
+        }
+        return Ok(());
+    }
 
-    let _result: i32 = (&input.parsed).iter()
-        //.map(|x| i32::from(levels_safe(x))).sum();
-        .map(func).filter(|&x| x).count() as i32;
+    let reader = open_input(&args.input_path)?;
 
-    println!("Result: {_result}");
+    let mut verdict_file = match &args.verdict_path
+    {
+        Some(path) => Some(io::BufWriter::new(File::create(path)?)),
+        None => None,
+    };
+
+    let check_before = std::time::Instant::now();
+    let counts = evaluate_reports(reader, args.tolerance, &args.policy, args.algorithm,
+        verdict_file.as_mut().map(|file| file as &mut dyn Write))?;
+    let check_duration = check_before.elapsed();
+
+    if let Some(mut file) = verdict_file
+    {
+        file.flush()?;
+    }
 
-    return Ok(());
+    println!("Safe reports: {}", counts.safe_cnt);
+    println!("Dampened reports: {}", counts.dampened_cnt);
+    println!("Unsafe reports: {}", counts.unsafe_cnt);
+    println!();
+    println!("Result: {}", counts.safe_cnt);
+    println!("Bonus result: {}", counts.safe_cnt + counts.dampened_cnt);
+    println!("Duration ({:?}): {check_duration:#?}", args.algorithm);
+
+    Ok(())
 }
 
 #[cfg(test)]
@@ -327,106 +748,408 @@ mod tests
 {
     use super::*;
 
+    fn levels_safe_with_tolerance(levels: &[i32], k: usize, policy: &SafetyPolicy) -> bool
+    // report is safe if at most k levels can be removed to make it safe
+    {
+        !matches!(check_report(levels, k, policy, Algorithm::Dynamic), Verdict::Unsafe { .. })
+    }
+
+    fn levels_safe(levels: &[i32], policy: &SafetyPolicy) -> bool
+    {
+        levels_safe_with_tolerance(levels, 0, policy)
+    }
+
+    fn levels_safe_bonus(levels: &[i32], policy: &SafetyPolicy) -> bool
+    // Problem Dampener, single bad level can be removed
+    {
+        levels_safe_with_tolerance(levels, 1, policy)
+    }
+
     // TODO: for the love of god, find a way to parametrize tests
     #[test]
     fn eq_begin_rising() 
     {
-        assert_eq!(levels_safe_bonus(&vec![1,1,2,3]), true);
+        assert!(levels_safe_bonus(&[1,1,2,3], &SafetyPolicy::default()));
     }
     
     #[test]
     fn eq_begin_falling() 
     {
-        assert_eq!(levels_safe_bonus(&vec![3,3,2,1]), true);
+        assert!(levels_safe_bonus(&[3,3,2,1], &SafetyPolicy::default()));
     }
 
     #[test]
     fn eq_end_rising() 
     {
-        assert_eq!(levels_safe_bonus(&vec![1,2,3,3]), true);
+        assert!(levels_safe_bonus(&[1,2,3,3], &SafetyPolicy::default()));
     }
 
     #[test]
     fn eq_end_falling() 
     {
-        assert_eq!(levels_safe_bonus(&vec![5,4,3,3]), true);
+        assert!(levels_safe_bonus(&[5,4,3,3], &SafetyPolicy::default()));
     }
 
     #[test]
     fn eq_mid_rising() 
     {
-        assert_eq!(levels_safe_bonus(&vec![1,2,3,3,4]), true);
+        assert!(levels_safe_bonus(&[1,2,3,3,4], &SafetyPolicy::default()));
     }
 
     #[test]
     fn eq_mid_falling() 
     {
-        assert_eq!(levels_safe_bonus(&vec![5,4,3,3,2,1]), true);
+        assert!(levels_safe_bonus(&[5,4,3,3,2,1], &SafetyPolicy::default()));
     }
 
     #[test]
     fn up_down_up() 
     {
-        assert_eq!(levels_safe_bonus(&vec![2,4,1,5]), true);
+        assert!(levels_safe_bonus(&[2,4,1,5], &SafetyPolicy::default()));
     }
 
     #[test]
     fn up_down_up2() 
     {
-        assert_eq!(levels_safe_bonus(&vec![2,7,4,6]), true);
+        assert!(levels_safe_bonus(&[2,7,4,6], &SafetyPolicy::default()));
     }
 
     #[test]
     fn down_up_down() 
     {
-        assert_eq!(levels_safe_bonus(&vec![6,4,7,3]), true);
+        assert!(levels_safe_bonus(&[6,4,7,3], &SafetyPolicy::default()));
     }
 
     #[test]
     fn fail1() 
     {
-        assert_eq!(levels_safe_bonus(&vec![1,2,5,4,3,2,1]), false);
+        assert!(!levels_safe_bonus(&[1,2,5,4,3,2,1], &SafetyPolicy::default()));
     }
 
     #[test]
     fn multiple_equal() 
     {
-        assert_eq!(levels_safe_bonus(&vec![1,2,2,2,2,3,4]), false);
+        assert!(!levels_safe_bonus(&[1,2,2,2,2,3,4], &SafetyPolicy::default()));
     }
 
     #[test]
     fn fail2() 
     {
-        assert_eq!(levels_safe_bonus(&vec![2,1,4,5,3]), false);
+        assert!(!levels_safe_bonus(&[2,1,4,5,3], &SafetyPolicy::default()));
     }
 
     #[test]
     fn fail3() 
     {
-        assert_eq!(levels_safe_bonus(&vec![9,7,5,3,3,3]), false);
+        assert!(!levels_safe_bonus(&[9,7,5,3,3,3], &SafetyPolicy::default()));
     }
 
     #[test]
     fn fail4() 
     {
-        assert_eq!(levels_safe_bonus(&vec![10, 8,4,5,9]), false);
+        assert!(!levels_safe_bonus(&[10, 8,4,5,9], &SafetyPolicy::default()));
     }
 
     #[test]
     fn eq_many() 
     {
-        assert_eq!(levels_safe_bonus(&vec![1,1,1,1,1,2]), false);
+        assert!(!levels_safe_bonus(&[1,1,1,1,1,2], &SafetyPolicy::default()));
     }
 
     #[test]
     fn second_invalid() 
     {
-        assert_eq!(levels_safe_bonus(&vec![7,10,8,10,11]), true);
+        assert!(levels_safe_bonus(&[7,10,8,10,11], &SafetyPolicy::default()));
     }
 
     #[test]
     fn fail123() 
     {
-        assert_eq!(levels_safe_bonus(&vec![0, 4,5,3,2,1]), false);
+        assert!(!levels_safe_bonus(&[0, 4,5,3,2,1], &SafetyPolicy::default()));
+    }
+
+    #[test]
+    fn tolerance_zero()
+    {
+        assert!(levels_safe_with_tolerance(&[7,6,4,2,1], 0, &SafetyPolicy::default()));
+        assert!(!levels_safe_with_tolerance(&[1,3,2,4,5], 0, &SafetyPolicy::default()));
+        assert!(!levels_safe_with_tolerance(&[8,6,4,4,1], 0, &SafetyPolicy::default()));
+    }
+
+    #[test]
+    fn tolerance_two()
+    {
+        assert!(!levels_safe_with_tolerance(&[1,2,9,9,3,4], 1, &SafetyPolicy::default()));
+        assert!(levels_safe_with_tolerance(&[1,2,9,9,3,4], 2, &SafetyPolicy::default()));
+        assert!(levels_safe_with_tolerance(&[9,1,2,9,3,4], 2, &SafetyPolicy::default()));
+        assert!(!levels_safe_with_tolerance(&[9,1,9,2,9,3,9], 2, &SafetyPolicy::default()));
+    }
+
+    #[test]
+    fn tolerance_short_report()
+    {
+        assert!(levels_safe_with_tolerance(&[], 0, &SafetyPolicy::default()));
+        assert!(levels_safe_with_tolerance(&[5], 0, &SafetyPolicy::default()));
+        assert!(levels_safe_with_tolerance(&[1,9], 1, &SafetyPolicy::default()));
+        assert!(!levels_safe_with_tolerance(&[1,9], 0, &SafetyPolicy::default()));
+    }
+
+    #[test]
+    fn policy_larger_steps()
+    {
+        let policy = SafetyPolicy { max_step: 5, ..SafetyPolicy::default() };
+        assert!(levels_safe(&[1,6,10,12], &policy));
+        assert!(!levels_safe(&[1,7,10,12], &policy));
+    }
+
+    #[test]
+    fn policy_direction()
+    {
+        let increasing = SafetyPolicy { direction: Direction::Increasing, ..SafetyPolicy::default() };
+        assert!(levels_safe(&[1,2,3], &increasing));
+        assert!(!levels_safe(&[3,2,1], &increasing));
+        // dampener can't help with a whole falling report
+        assert!(!levels_safe_bonus(&[5,3,2,1], &increasing));
+        assert!(levels_safe_bonus(&[1,3,2,4], &increasing));
+    }
+
+    #[test]
+    fn policy_plateaus()
+    {
+        let plateaus = SafetyPolicy { allow_plateaus: true, ..SafetyPolicy::default() };
+        assert!(levels_safe(&[1,2,2,2,3], &plateaus));
+        assert!(levels_safe(&[3,3,2,1,1], &plateaus));
+        assert!(!levels_safe(&[1,2,2,1], &plateaus));
+    }
+
+    #[test]
+    fn policy_direction_changes()
+    {
+        let one_change = SafetyPolicy { max_direction_changes: 1, ..SafetyPolicy::default() };
+        assert!(levels_safe(&[1,2,4,3,1], &one_change));
+        assert!(!levels_safe(&[1,2,4,3,5], &one_change));
+        assert!(!levels_safe_bonus(&[1,2,4,3,5,4,6], &one_change));
+        assert!(levels_safe_with_tolerance(&[1,2,4,3,5,4,6], 2, &one_change));
+    }
+
+    #[test]
+    fn policy_from_args()
+    {
+        let args = parse_args(["--max-step", "4", "--direction", "decreasing", "--plateaus", "in.txt"]
+            .iter().map(|x| x.to_string())).unwrap();
+        assert_eq!(args.input_path, "in.txt");
+        assert_eq!(args.policy.max_step, 4);
+        assert_eq!(args.policy.direction, Direction::Decreasing);
+        assert!(args.policy.allow_plateaus);
+
+        assert!(parse_args(["--min-step", "4", "--max-step", "2"].iter().map(|x| x.to_string())).is_err());
+        assert!(parse_args(["--direction", "sideways"].iter().map(|x| x.to_string())).is_err());
+    }
+
+    #[test]
+    fn verdict_safe()
+    {
+        assert_eq!(check_report(&[7,6,4,2,1], 1, &SafetyPolicy::default(), Algorithm::Dynamic), Verdict::Safe);
+    }
+
+    #[test]
+    fn verdict_dampened()
+    {
+        assert_eq!(check_report(&[1,3,2,4,5], 1, &SafetyPolicy::default(), Algorithm::Dynamic),
+            Verdict::Dampened(vec![RemovedLevel { index: 1, value: 3 }]));
+        assert_eq!(check_report(&[8,6,4,4,1], 1, &SafetyPolicy::default(), Algorithm::Dynamic),
+            Verdict::Dampened(vec![RemovedLevel { index: 2, value: 4 }]));
+        assert_eq!(check_report(&[9,1,2,9,3,4], 2, &SafetyPolicy::default(), Algorithm::Dynamic),
+            Verdict::Dampened(vec![RemovedLevel { index: 0, value: 9 }, RemovedLevel { index: 3, value: 9 }]));
+    }
+
+    #[test]
+    fn verdict_unsafe_reasons()
+    {
+        let policy = SafetyPolicy::default();
+        assert_eq!(check_report(&[1,2,7,8,9], 1, &policy, Algorithm::Dynamic),
+            Verdict::Unsafe { index: 1, pair: (2, 7), reason: Violation::StepTooLarge });
+        assert_eq!(check_report(&[1,2,2,2,3], 1, &policy, Algorithm::Dynamic),
+            Verdict::Unsafe { index: 1, pair: (2, 2), reason: Violation::Plateau });
+        assert_eq!(check_report(&[1,3,2,4,3], 1, &policy, Algorithm::Dynamic),
+            Verdict::Unsafe { index: 1, pair: (3, 2), reason: Violation::DirectionChange });
+
+        let increasing = SafetyPolicy { direction: Direction::Increasing, min_step: 2, ..SafetyPolicy::default() };
+        assert_eq!(check_report(&[5,3,1], 0, &increasing, Algorithm::Dynamic),
+            Verdict::Unsafe { index: 0, pair: (5, 3), reason: Violation::WrongDirection });
+        assert_eq!(check_report(&[1,3,4,5], 0, &increasing, Algorithm::Dynamic),
+            Verdict::Unsafe { index: 1, pair: (3, 4), reason: Violation::StepTooSmall });
+    }
+
+    #[test]
+    fn verdict_display()
+    {
+        assert_eq!(check_report(&[1,3,2,4,5], 1, &SafetyPolicy::default(), Algorithm::Dynamic).to_string(),
+            "safe after removing level 1 (value 3)");
+        assert_eq!(check_report(&[1,2,7,8,9], 1, &SafetyPolicy::default(), Algorithm::Dynamic).to_string(),
+            "unsafe, step too large between levels 1 and 2 (2 -> 7)");
+    }
+
+    fn all_reports(len: usize, max_level: i32) -> Vec<Vec<i32>>
+    // every report of given length with levels 1..=max_level
+    {
+        (0..len).fold(vec![vec![]], |reports, _|
+            reports.iter()
+                .flat_map(|report| (1..=max_level).map(move |level|
+                    {
+                        let mut extended = report.clone();
+                        extended.push(level);
+                        extended
+                    }))
+                .collect())
+    }
+
+    fn cross_check(policy: &SafetyPolicy, max_tolerance: usize)
+    {
+        for len in 0..=6
+        {
+            for report in all_reports(len, 5)
+            {
+                for k in 0..=max_tolerance
+                {
+                    let dynamic = dampen(&report, k, policy);
+                    let oracle = dampen_brute_force(&report, k, policy);
+
+                    // removed levels may differ on ties, but never their count
+                    assert_eq!(dynamic.as_ref().map(Vec::len), oracle.as_ref().map(Vec::len),
+                        "{report:?}, k = {k}, {policy:?}");
+
+                    // and the remaining levels have to be safe
+                    if let Some(removed) = dynamic
+                    {
+                        let remaining: Vec<i32> = report.iter().enumerate()
+                            .filter(|(idx, _)| !removed.contains(idx))
+                            .map(|(_, level)| *level)
+                            .collect();
+                        assert_eq!(first_violation(&remaining, policy), None, "{report:?}, k = {k}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn cross_check_default()
+    {
+        cross_check(&SafetyPolicy::default(), 3);
+    }
+
+    #[test]
+    fn cross_check_policies()
+    {
+        cross_check(&SafetyPolicy { allow_plateaus: true, max_step: 2, ..SafetyPolicy::default() }, 2);
+        cross_check(&SafetyPolicy { direction: Direction::Decreasing, min_step: 2, ..SafetyPolicy::default() }, 2);
+        cross_check(&SafetyPolicy { max_direction_changes: 2, allow_plateaus: true, ..SafetyPolicy::default() }, 2);
+    }
+
+    #[test]
+    fn brute_force_verdicts()
+    {
+        let policy = SafetyPolicy::default();
+        assert_eq!(check_report(&[1,3,2,4,5], 1, &policy, Algorithm::BruteForce),
+            Verdict::Dampened(vec![RemovedLevel { index: 1, value: 3 }]));
+        assert_eq!(check_report(&[1,2,7,8,9], 1, &policy, Algorithm::BruteForce),
+            Verdict::Unsafe { index: 1, pair: (2, 7), reason: Violation::StepTooLarge });
+    }
+
+    #[test]
+    fn algorithm_from_args()
+    {
+        let args = parse_args(["--algorithm", "brute-force"].iter().map(|x| x.to_string())).unwrap();
+        assert_eq!(args.algorithm, Algorithm::BruteForce);
+        assert!(parse_args(["--algorithm", "derivative"].iter().map(|x| x.to_string())).is_err());
+    }
+
+    #[test]
+    fn evaluate_example()
+    {
+        let example = "7 6 4 2 1\n1 2 7 8 9\n9 7 6 2 1\n1 3 2 4 5\n8 6 4 4 1\n1 3 6 7 9\n\n";
+        let mut verdicts: Vec<u8> = Vec::new();
+
+        let counts = evaluate_reports(io::Cursor::new(example), 1, &SafetyPolicy::default(),
+            Algorithm::Dynamic, Some(&mut verdicts)).unwrap();
+
+        assert_eq!(counts, ReportCounts { safe_cnt: 2, dampened_cnt: 2, unsafe_cnt: 2 });
+        assert_eq!(String::from_utf8(verdicts).unwrap().lines().collect::<Vec<_>>(), vec![
+            "7 6 4 2 1: safe",
+            "1 2 7 8 9: unsafe, step too large between levels 1 and 2 (2 -> 7)",
+            "9 7 6 2 1: unsafe, step too large between levels 2 and 3 (6 -> 2)",
+            "1 3 2 4 5: safe after removing level 1 (value 3)",
+            "8 6 4 4 1: safe after removing level 2 (value 4)",
+            "1 3 6 7 9: safe",
+        ]);
+    }
+
+    #[test]
+    fn evaluate_keeps_order_across_batches()
+    {
+        let reports: Vec<String> = (0..BATCH_SIZE * 2 + 7).map(|i| format!("{} {} 1", i, i + 1)).collect();
+        let mut verdicts: Vec<u8> = Vec::new();
+
+        let counts = evaluate_reports(io::Cursor::new(reports.join("\n")), 1, &SafetyPolicy::default(),
+            Algorithm::Dynamic, Some(&mut verdicts)).unwrap();
+
+        assert_eq!(counts.safe_cnt + counts.dampened_cnt + counts.unsafe_cnt, reports.len());
+        let written: Vec<String> = String::from_utf8(verdicts).unwrap().lines()
+            .map(|line| line.split(':').next().unwrap().to_string())
+            .collect();
+        assert_eq!(written, reports);
+    }
+
+    #[test]
+    fn evaluate_invalid_number()
+    {
+        let result = evaluate_reports(io::Cursor::new("1 2 3\n1 x 3\n"), 1, &SafetyPolicy::default(),
+            Algorithm::Dynamic, None);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn stats_example()
+    {
+        let input = Input { parsed: vec![
+            vec![7,6,4,2,1], vec![1,2,7,8,9], vec![9,7,6,2,1],
+            vec![1,3,2,4,5], vec![8,6,4,4,1], vec![1,3,6,7,9],
+        ]};
+
+        let stats = compute_stats(&input, 2, &SafetyPolicy::default(), Algorithm::Dynamic);
+
+        assert_eq!(stats.report_cnt, 6);
+        assert_eq!(stats.lengths, BTreeMap::from([(5, 6)]));
+        assert_eq!(stats.steps, BTreeMap::from([(0, 1), (1, 10), (2, 9), (3, 2), (4, 1), (5, 1)]));
+        assert_eq!(stats.direction_changes, BTreeMap::from([(0, 5), (2, 1)]));
+        assert_eq!(stats.safe_by_tolerance, vec![2, 4, 6]);
+    }
+
+    #[test]
+    fn stats_json()
+    {
+        let input = Input { parsed: vec![vec![1,2,3], vec![3,3]] };
+        let stats = compute_stats(&input, 1, &SafetyPolicy::default(), Algorithm::Dynamic);
+
+        assert_eq!(serde_json::to_string(&stats).unwrap(), concat!(
+            r#"{"report_cnt":2,"lengths":{"2":1,"3":1},"steps":{"0":1,"1":2},"#,
+            r#""direction_changes":{"0":2},"safe_by_tolerance":[1,2]}"#));
+    }
+
+    #[test]
+    fn stats_mode_from_args()
+    {
+        let args = parse_args(["--stats", "in.txt", "--json", "--max-tolerance", "5"]
+            .iter().map(|x| x.to_string())).unwrap();
+        assert_eq!(args.mode, Mode::Stats);
+        assert_eq!(args.input_path, "in.txt");
+        assert_eq!(args.max_tolerance, 5);
+        assert!(args.json);
+
+        // input file can be called stats too
+        let args = parse_args(["stats"].iter().map(|x| x.to_string())).unwrap();
+        assert_eq!(args.mode, Mode::Evaluate);
+        assert_eq!(args.input_path, "stats");
     }
 }
@@ -1,6 +1,10 @@
 use std::error::Error;
+use std::fmt;
 use std::fs;
+use std::iter::zip;
 use std::io::{self, Read};
+use std::ops::Range;
+use std::time::Instant;
 use regex::{self, Regex};
 
 fn get_input(file_path: &str) -> Result<String, io::Error>
@@ -16,89 +20,1351 @@ fn get_input(file_path: &str) -> Result<String, io::Error>
     let mut reader = io::BufReader::new(file);
 
     let mut result: String = String::new();
-    
+
     // TODO: &mut ??? mutable borrow
-    return match reader.read_to_string(&mut result)
+    reader.read_to_string(&mut result)?;
+    Ok(result)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Effect
+{
+    // multiply operands and add the product to the accumulator
+    Product,
+    // add operands to the accumulator
+    Sum,
+    Enable,
+    Disable,
+    Toggle,
+    // set accumulator back to zero
+    Reset,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct InstructionSpec
+{
+    name: String,
+    arity: usize,
+    // allowed number of digits of each operand
+    #[serde(default = "default_min_digits")]
+    min_digits: usize,
+    #[serde(default = "default_max_digits")]
+    max_digits: usize,
+    effect: Effect,
+}
+
+fn default_min_digits() -> usize { 1 }
+fn default_max_digits() -> usize { 3 }
+
+impl InstructionSpec
+{
+    fn new(name: &str, arity: usize, effect: Effect) -> Self
     {
-        // mut String -> String
-        Ok(_) => Ok(result.to_string()),
-        Err(e) => Err(e)
-    };
+        InstructionSpec { name: name.to_string(), arity, min_digits: default_min_digits(),
+            max_digits: default_max_digits(), effect }
+    }
+}
+
+fn default_instruction_set() -> Vec<InstructionSpec>
+// new instruction only needs a new line here (or in a config file),
+// the tokenizer regex is built from the names
+{
+    vec![
+        InstructionSpec::new("mul", 2, Effect::Product),
+        InstructionSpec::new("do", 0, Effect::Enable),
+        InstructionSpec::new("don't", 0, Effect::Disable),
+    ]
+}
+
+#[derive(serde::Deserialize)]
+struct InstructionSet
+{
+    instruction: Vec<InstructionSpec>,
+}
+
+fn load_instruction_set(file_path: &str) -> Result<Vec<InstructionSpec>, Box<dyn Error>>
+// TOML file with one [[instruction]] table per instruction, e.g.
+//
+// [[instruction]]
+// name = "add"
+// arity = 3
+// max_digits = 4       # optional, min_digits too
+// effect = "sum"       # product, sum, enable, disable, toggle or reset
+{
+    let set: InstructionSet = toml::from_str(&fs::read_to_string(file_path)?)?;
+    check_instruction_set(&set.instruction)?;
+    Ok(set.instruction)
+}
+
+fn check_instruction_set(table: &[InstructionSpec]) -> Result<(), Box<dyn Error>>
+{
+    for (idx, spec) in table.iter().enumerate()
+    {
+        let problem = if spec.name.is_empty() || spec.name.contains(['(', ')', ','])
+        {
+            Some("name can't be empty or contain parentheses and commas".to_string())
+        }
+        else if table[..idx].iter().any(|other| other.name == spec.name)
+        {
+            Some("name is defined twice".to_string())
+        }
+        else if spec.arity > MAX_ARITY
+        {
+            Some(format!("at most {MAX_ARITY} operands are supported"))
+        }
+        else if spec.min_digits == 0 || spec.min_digits > spec.max_digits || spec.max_digits > MAX_OPERAND_DIGITS
+        {
+            Some(format!("operand digits have to be between 1 and {MAX_OPERAND_DIGITS}"))
+        }
+        else
+        {
+            None
+        };
+
+        if let Some(problem) = problem
+        {
+            return Err(Box::new(io::Error::new(io::ErrorKind::InvalidInput,
+                format!("Invalid instruction \"{}\": {problem}", spec.name))));
+        }
+    }
+
+    Ok(())
+}
+
+// longest operand that still fits into i64
+const MAX_OPERAND_DIGITS: usize = 18;
+// operands are kept inline, so scanning doesn't allocate
+const MAX_ARITY: usize = 4;
+// bytes read from a stream at once
+const CHUNK_SIZE: usize = 64 * 1024;
+// bytes of input scanned by one parallel task
+const PARALLEL_CHUNK_SIZE: usize = 4 * 1024 * 1024;
+
+#[derive(Clone, Copy, Debug)]
+struct Instruction<'a>
+{
+    spec: &'a InstructionSpec,
+    // only first spec.arity operands are valid
+    operands: [i64; MAX_ARITY],
+    // byte offset of the instruction name in the input
+    offset: usize,
+    // length in bytes, including the parentheses
+    len: usize,
+}
+
+impl Instruction<'_>
+{
+    fn operands(&self) -> &[i64]
+    {
+        &self.operands[..self.spec.arity]
+    }
+}
+
+fn tokenizer_regex(table: &[InstructionSpec]) -> Result<Regex, regex::Error>
+// candidate is any known name followed by parentheses with digits and commas,
+// arity and operand length are checked afterwards
+{
+    let mut names: Vec<&str> = table.iter().map(|spec| spec.name.as_str()).collect();
+    // longer names first, so "don't" is not cut short by "do"
+    names.sort_by_key(|name| std::cmp::Reverse(name.len()));
+
+    let alternatives: Vec<String> = names.iter().map(|name| regex::escape(name)).collect();
+    Regex::new(&format!(r"(?<name>{})\((?<args>[0-9,]*)\)", alternatives.join("|")))
+}
+
+fn parse_operands(args: &str, spec: &InstructionSpec) -> Option<[i64; MAX_ARITY]>
+// None if the candidate is not a valid instruction
+{
+    let mut operands = [0; MAX_ARITY];
+
+    if spec.arity == 0
+    {
+        return if args.is_empty() { Some(operands) } else { None };
+    }
+
+    let mut split = args.split(',');
+
+    for operand in operands.iter_mut().take(spec.arity)
+    {
+        let x = split.next()?;
+
+        if x.len() < spec.min_digits || x.len() > spec.max_digits
+        {
+            return None;
+        }
+        *operand = x.parse::<i64>().ok()?;
+    }
+
+    match split.next()
+    {
+        Some(_) => None,
+        None => Some(operands),
+    }
+}
+
+fn tokenize_regex<'a>(input: &str, table: &'a [InstructionSpec]) -> Result<Vec<Instruction<'a>>, Box<dyn Error>>
+// original regex based tokenizer, kept as a reference for the scanner
+{
+    let re = tokenizer_regex(table)?;
+    let mut instructions: Vec<Instruction> = Vec::new();
+
+    for captures in re.captures_iter(input)
+    {
+        let name = &captures["name"];
+
+        // regex only matches names from the table
+        let spec = table.iter().find(|spec| spec.name == name).unwrap();
+
+        if let Some(operands) = parse_operands(&captures["args"], spec)
+        {
+            let found = captures.get(0).unwrap();
+            instructions.push(Instruction { spec, operands, offset: found.start(), len: found.len() });
+        }
+    }
+
+    Ok(instructions)
+}
+
+enum Candidate<'a>
+{
+    Valid(Instruction<'a>),
+    Invalid,
+    // ran out of bytes before deciding, more input might make it valid
+    Incomplete,
+}
+
+enum Scan<'a>
+{
+    Found(Instruction<'a>),
+    // candidate starting at given position needs more input
+    Pending(usize),
+    End,
+}
+
+struct Scanner<'a>
+{
+    table: &'a [InstructionSpec],
+    // quick rejection of positions where no instruction name starts
+    first_bytes: [bool; 256],
+    // longest possible instruction, bytes carried over between stream chunks
+    max_len: usize,
+}
+
+impl<'a> Scanner<'a>
+{
+    fn new(table: &'a [InstructionSpec]) -> Result<Self, Box<dyn Error>>
+    {
+        check_instruction_set(table)?;
+
+        let mut first_bytes = [false; 256];
+        let mut max_len = 0;
+
+        for spec in table
+        {
+            first_bytes[spec.name.as_bytes()[0] as usize] = true;
+            // name(digits,digits,...)
+            max_len = max_len.max(spec.name.len() + 2 + spec.arity * (spec.max_digits + 1));
+        }
+
+        Ok(Scanner { table, first_bytes, max_len })
+    }
+
+    fn match_spec(&self, spec: &'a InstructionSpec, bytes: &[u8], pos: usize) -> Candidate<'a>
+    // same as regex name\(\d{min,max},...\) anchored at pos
+    {
+        let name = spec.name.as_bytes();
+        let rest = &bytes[pos..];
+
+        if rest.len() < name.len()
+        {
+            return if name.starts_with(rest) { Candidate::Incomplete } else { Candidate::Invalid };
+        }
+
+        if &rest[..name.len()] != name
+        {
+            return Candidate::Invalid;
+        }
+
+        let mut idx = name.len();
+        let mut operands = [0; MAX_ARITY];
+
+        // opening parenthesis, separating commas and closing parenthesis
+        let delimiters = std::iter::once(b'(')
+            .chain(std::iter::repeat_n(b',', spec.arity.saturating_sub(1)));
+
+        for (operand_idx, delimiter) in delimiters.enumerate()
+        {
+            match rest.get(idx)
+            {
+                None => return Candidate::Incomplete,
+                Some(&x) if x == delimiter => idx += 1,
+                Some(_) => return Candidate::Invalid,
+            }
+
+            if operand_idx == spec.arity
+            {
+                // arity 0, only parentheses
+                break;
+            }
+
+            let mut digits = 0;
+            let mut value = 0;
+
+            while let Some(x) = rest.get(idx).filter(|x| x.is_ascii_digit())
+            {
+                digits += 1;
+
+                if digits > spec.max_digits
+                {
+                    return Candidate::Invalid;
+                }
+
+                value = value * 10 + (x - b'0') as i64;
+                idx += 1;
+            }
+
+            if idx == rest.len()
+            {
+                return Candidate::Incomplete;
+            }
+
+            if digits < spec.min_digits
+            {
+                return Candidate::Invalid;
+            }
+
+            operands[operand_idx] = value;
+        }
+
+        match rest.get(idx)
+        {
+            None => Candidate::Incomplete,
+            Some(b')') => Candidate::Valid(Instruction { spec, operands, offset: pos, len: idx + 1 }),
+            Some(_) => Candidate::Invalid,
+        }
+    }
+
+    fn next_match(&self, bytes: &[u8], from: usize, eof: bool) -> Scan<'a>
+    // leftmost instruction starting at or after from, like regex find_at
+    {
+        for pos in from..bytes.len()
+        {
+            if !self.first_bytes[bytes[pos] as usize]
+            {
+                continue;
+            }
+
+            let mut incomplete = false;
+
+            // names can't contain parentheses, so at most one spec matches at one position
+            for spec in self.table
+            {
+                match self.match_spec(spec, bytes, pos)
+                {
+                    Candidate::Valid(instruction) => return Scan::Found(instruction),
+                    Candidate::Incomplete => incomplete = true,
+                    Candidate::Invalid => (),
+                }
+            }
+
+            if incomplete && !eof
+            {
+                return Scan::Pending(pos);
+            }
+        }
+
+        Scan::End
+    }
+
+    fn scan<'b>(&'b self, bytes: &'b [u8]) -> impl Iterator<Item = Instruction<'a>> + 'b
+    // all instructions in a complete input
+    {
+        self.scan_range(bytes, 0..bytes.len())
+    }
+
+    fn scan_range<'b>(&'b self, bytes: &'b [u8], range: Range<usize>) -> impl Iterator<Item = Instruction<'a>> + 'b
+    // instructions starting inside the range of a complete input, the last one can reach past its end
+    {
+        // every candidate starting in the range is decided within max_len bytes
+        let window = &bytes[..bytes.len().min(range.end + self.max_len)];
+        let mut pos = range.start;
+
+        std::iter::from_fn(move ||
+            match self.next_match(window, pos, true)
+            {
+                Scan::Found(instruction) if instruction.offset < range.end =>
+                {
+                    pos = instruction.offset + instruction.len;
+                    Some(instruction)
+                },
+                _ => None,
+            })
+    }
+
+    fn is_chunkable(&self) -> bool
+    // scanning can start anywhere only if no instruction can start inside another one,
+    // names must not contain digits and must not occur inside other names
+    {
+        let no_digits = self.table.iter().all(|spec| !spec.name.bytes().any(|x| x.is_ascii_digit()));
+        let no_nesting = self.table.iter().all(|outer|
+            self.table.iter().all(|inner| outer.name.match_indices(inner.name.as_str()).all(|(idx, _)| idx == 0)));
+
+        no_digits && no_nesting
+    }
+
+    fn scan_stream(&self, mut reader: impl Read, mut handle: impl FnMut(&Instruction<'a>) -> Result<(), Box<dyn Error>>)
+        -> Result<(), Box<dyn Error>>
+    // instructions from input read in chunks, one can straddle the boundary of two chunks
+    // buffer is allocated once, unfinished candidate is moved to its beginning
+    {
+        let mut buffer = vec![0u8; CHUNK_SIZE.max(2 * self.max_len)];
+        // bytes in buffer, offset of buffer start in the whole input
+        let (mut filled, mut base_offset) = (0, 0);
+        let mut eof = false;
+
+        while !eof
+        {
+            let read = reader.read(&mut buffer[filled..])?;
+            eof = read == 0;
+            filled += read;
+
+            let mut pos = 0;
+
+            loop
+            {
+                match self.next_match(&buffer[..filled], pos, eof)
+                {
+                    Scan::Found(instruction) =>
+                    {
+                        pos = instruction.offset + instruction.len;
+                        handle(&Instruction { offset: base_offset + instruction.offset, ..instruction })?;
+                    },
+                    Scan::Pending(pending) =>
+                    {
+                        pos = pending;
+                        break;
+                    },
+                    Scan::End =>
+                    {
+                        pos = filled;
+                        break;
+                    },
+                }
+            }
+
+            buffer.copy_within(pos..filled, 0);
+            filled -= pos;
+            base_offset += pos;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum ComputeError
+{
+    // value or total doesn't fit into i128, offset of the instruction causing it
+    Overflow { offset: usize },
+}
+
+impl fmt::Display for ComputeError
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match self
+        {
+            ComputeError::Overflow { offset } => write!(f, "Arithmetic overflow in instruction at offset {offset}"),
+        }
+    }
+}
+
+impl Error for ComputeError {}
+
+#[derive(Clone, Copy, Debug)]
+struct Machine
+{
+    enabled: bool,
+    // sum of products while enabled - part 2
+    accumulator: i128,
+    // sum of all products, enabled or not - part 1
+    unconditional: i128,
 }
 
-fn compute(input: &str) -> Result<i32, Box<dyn Error>>
+impl Machine
 {
-    let re = Regex::new(r"mul\((?<x>\d{1,3}),(?<y>\d{1,3})\)")?;
-    let mut result: i32 = 0;
+    fn new() -> Self
+    {
+        Machine { enabled: true, accumulator: 0, unconditional: 0 }
+    }
+
+    fn execute(&mut self, instruction: &Instruction) -> Result<Option<i128>, ComputeError>
+    // value added by the instruction, even if it was skipped because machine is disabled
+    {
+        let overflow = ComputeError::Overflow { offset: instruction.offset };
+        let mut operands = instruction.operands().iter().map(|&x| x as i128);
+
+        let value = match instruction.spec.effect
+        {
+            Effect::Product => operands.try_fold(1i128, |acc, x| acc.checked_mul(x)),
+            Effect::Sum => operands.try_fold(0i128, |acc, x| acc.checked_add(x)),
+            Effect::Enable => { self.enabled = true; return Ok(None) },
+            Effect::Disable => { self.enabled = false; return Ok(None) },
+            Effect::Toggle => { self.enabled = !self.enabled; return Ok(None) },
+            Effect::Reset =>
+            {
+                self.accumulator = 0;
+                self.unconditional = 0;
+                return Ok(None);
+            },
+        }.ok_or(overflow)?;
+
+        // skipped values still count towards part 1, so they can overflow too
+        self.unconditional = self.unconditional.checked_add(value).ok_or(overflow)?;
+
+        if self.enabled
+        {
+            self.accumulator = self.accumulator.checked_add(value).ok_or(overflow)?;
+        }
+        Ok(Some(value))
+    }
+}
+
+fn run(input: &str, table: &[InstructionSpec]) -> Result<Machine, Box<dyn Error>>
+{
+    let scanner = Scanner::new(table)?;
+    let mut machine = Machine::new();
 
-    for captures in re.captures_iter(&input)
+    for instruction in scanner.scan(input.as_bytes())
     {
-        result += compute_capture(&captures["x"], &captures["y"])?;
+        machine.execute(&instruction)?;
     }
 
-    return Ok(result);
+    Ok(machine)
+}
+
+#[derive(Debug)]
+struct ChunkRun
+// chunk executed from zero totals
+{
+    // totals right before the first reset, None if there is none, totals after
+    // the reset don't depend on the previous chunks, but these still have to fit
+    before_reset: Option<Machine>,
+    end: Machine,
+}
+
+#[derive(Debug)]
+struct ChunkSummary
+{
+    // indexed by the enabled state at the start of the chunk, None on overflow
+    runs: [Option<ChunkRun>; 2],
+}
+
+fn summarize_chunk(scanner: &Scanner, bytes: &[u8], range: Range<usize>) -> ChunkSummary
+{
+    let instructions: Vec<Instruction> = scanner.scan_range(bytes, range).collect();
+
+    let execute_from = |enabled: bool|
+    {
+        let mut machine = Machine { enabled, accumulator: 0, unconditional: 0 };
+        let mut before_reset: Option<Machine> = None;
+
+        for instruction in &instructions
+        {
+            if instruction.spec.effect == Effect::Reset && before_reset.is_none()
+            {
+                before_reset = Some(machine);
+            }
+            machine.execute(instruction).ok()?;
+        }
+
+        Some(ChunkRun { before_reset, end: machine })
+    };
+
+    ChunkSummary { runs: [execute_from(false), execute_from(true)] }
 }
 
-fn compute_bonus(input: &str) -> Result<i32, Box<dyn Error>>
+fn run_parallel(mut reader: impl Read, table: &[InstructionSpec], chunk_size: usize) -> Result<Machine, Box<dyn Error>>
+// input is read in batches of chunks, chunks of a batch are summarized in parallel and then
+// stitched in order, each continuing from the state the previous one ended in, so only one
+// batch is kept in memory and the input doesn't have to be UTF-8
 {
-    let re = Regex::new(r"mul\((?<x>\d{1,3}),(?<y>\d{1,3})\)|do\(\)|don't\(\)")?;
-    let mut result: i32 = 0;
+    use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+
+    let scanner = Scanner::new(table)?;
+
+    if !scanner.is_chunkable()
+    {
+        return run_stream(reader, table);
+    }
 
-    let mut enabled: bool= true;
+    let chunk_size = chunk_size.max(1);
+    let batch_size = chunk_size * rayon::current_num_threads();
+    // batch followed by the first max_len bytes of the next one, instructions
+    // starting in the batch can reach into them
+    let wanted = batch_size + scanner.max_len;
+    let mut buffer: Vec<u8> = Vec::with_capacity(wanted);
+    // offset of buffer start in the whole input
+    let mut base_offset = 0;
+    let mut machine = Machine::new();
 
-    for capture in re.captures_iter(&input)
+    loop
     {
-        if capture[0].starts_with("mul") && enabled
-        { 
-            result += compute_capture(&capture["x"], &capture["y"])?; 
+        (&mut reader).take((wanted - buffer.len()) as u64).read_to_end(&mut buffer)?;
+        let eof = buffer.len() < wanted;
+        let batch_len = if eof { buffer.len() } else { batch_size };
+
+        let ranges: Vec<Range<usize>> = (0..batch_len).step_by(chunk_size)
+            .map(|start| start..batch_len.min(start + chunk_size))
+            .collect();
+
+        let summaries: Vec<ChunkSummary> = ranges.par_iter()
+            .map(|range| summarize_chunk(&scanner, &buffer, range.clone()))
+            .collect();
+
+        for (range, summary) in zip(ranges, summaries)
+        {
+            let stitched = summary.runs[machine.enabled as usize].as_ref().and_then(|run|
+            {
+                // values are never negative, so if the totals fit at the end (or right before the reset),
+                // they fit after every instruction as well
+                let carried = run.before_reset.unwrap_or(run.end);
+                let accumulator = machine.accumulator.checked_add(carried.accumulator)?;
+                let unconditional = machine.unconditional.checked_add(carried.unconditional)?;
+
+                match run.before_reset
+                {
+                    Some(_) => Some(run.end),
+                    None => Some(Machine { enabled: run.end.enabled, accumulator, unconditional }),
+                }
+            });
+
+            match stitched
+            {
+                Some(next) => machine = next,
+                // overflow somewhere in the chunk, only replaying it finds the instruction
+                None => for instruction in scanner.scan_range(&buffer, range)
+                {
+                    machine.execute(&Instruction { offset: base_offset + instruction.offset, ..instruction })?;
+                },
+            }
         }
-        else if capture[0].starts_with("don't()")
+
+        if eof
         {
-            enabled = false;     
+            return Ok(machine);
         }
-        else if capture[0].starts_with("do()")
+
+        // next batch starts with the bytes read ahead
+        buffer.drain(..batch_len);
+        base_offset += batch_len;
+    }
+}
+
+fn run_stream(reader: impl Read, table: &[InstructionSpec]) -> Result<Machine, Box<dyn Error>>
+{
+    let scanner = Scanner::new(table)?;
+    let mut machine = Machine::new();
+
+    scanner.scan_stream(reader, |instruction| { machine.execute(instruction)?; Ok(()) })?;
+    Ok(machine)
+}
+
+#[derive(Debug)]
+struct TraceEntry<'a>
+{
+    instruction: Instruction<'a>,
+    // 1-based, column counts characters, not bytes
+    line: usize,
+    column: usize,
+    // product or sum, None for instructions not changing the accumulator
+    value: Option<i128>,
+    // state after the instruction, for products and sums whether they were counted
+    enabled: bool,
+    // part 2 total after the instruction
+    total: i128,
+}
+
+fn trace<'a>(input: &str, table: &'a [InstructionSpec]) -> Result<Vec<TraceEntry<'a>>, Box<dyn Error>>
+{
+    let scanner = Scanner::new(table)?;
+    let mut machine = Machine::new();
+    let mut entries: Vec<TraceEntry> = Vec::new();
+
+    // position of the previous instruction, lines are counted only in between
+    let (mut cursor, mut line, mut column) = (0, 1, 1);
+
+    for instruction in scanner.scan(input.as_bytes())
+    {
+        for c in input[cursor..instruction.offset].chars()
         {
-            enabled = true;
+            if c == '\n'
+            {
+                line += 1;
+                column = 1;
+            }
+            else
+            {
+                column += 1;
+            }
         }
+        cursor = instruction.offset;
+
+        let value = machine.execute(&instruction)?;
+        entries.push(TraceEntry { instruction, line, column, value, enabled: machine.enabled, total: machine.accumulator });
     }
 
-    return Ok(result);
+    Ok(entries)
+}
+
+fn print_trace(input: &str, entries: &[TraceEntry])
+{
+    println!("{:>8} {:>10}  {:<16} {:>10} {:>8} {:>12}", "Offset", "Line:col", "Instruction", "Value", "Enabled", "Total");
+
+    for entry in entries
+    {
+        let instruction = &entry.instruction;
+        let text = &input[instruction.offset..instruction.offset + instruction.len];
+        let value = entry.value.map_or(String::new(), |x| x.to_string());
+
+        println!("{:>8} {:>10}  {:<16} {:>10} {:>8} {:>12}", instruction.offset,
+            format!("{}:{}", entry.line, entry.column), text, value,
+            if entry.enabled { "yes" } else { "no" }, entry.total);
+    }
 }
 
-fn compute_capture(x: &str, y: &str) -> Result<i32, Box<dyn Error>>
+// ANSI colours for the annotated input
+const COLOR_DISABLED: &str = "\x1b[2;31m";
+const COLOR_COUNTED: &str = "\x1b[1;32m";
+const COLOR_SKIPPED: &str = "\x1b[1;31m";
+const COLOR_TOGGLE: &str = "\x1b[1;33m";
+const COLOR_RESET: &str = "\x1b[0m";
+
+fn annotate(input: &str, entries: &[TraceEntry]) -> String
+// copy of the input, disabled regions dimmed, instructions highlighted by what they did
 {
-    let x: i32 = x.parse::<i32>()?;
-    let y: i32 = y.parse::<i32>()?;
-    let capture_result = x * y;
-    //println!("{x}, {y}: {capture_result}");
-    return Ok(capture_result);
+    let mut annotated = String::with_capacity(input.len() * 2);
+    let (mut cursor, mut enabled) = (0, true);
+
+    for entry in entries
+    {
+        let instruction = &entry.instruction;
+        let between = &input[cursor..instruction.offset];
+
+        match enabled
+        {
+            true => annotated.push_str(between),
+            false => annotated.push_str(&format!("{COLOR_DISABLED}{between}{COLOR_RESET}")),
+        }
+
+        let color = match (entry.value, entry.enabled)
+        {
+            (Some(_), true) => COLOR_COUNTED,
+            (Some(_), false) => COLOR_SKIPPED,
+            (None, _) => COLOR_TOGGLE,
+        };
+
+        cursor = instruction.offset + instruction.len;
+        annotated.push_str(&format!("{color}{}{COLOR_RESET}", &input[instruction.offset..cursor]));
+        enabled = entry.enabled;
+    }
+
+    match enabled
+    {
+        true => annotated.push_str(&input[cursor..]),
+        false => annotated.push_str(&format!("{COLOR_DISABLED}{}{COLOR_RESET}", &input[cursor..])),
+    }
 
+    annotated
 }
 
-fn _test_regex() -> Result<(), Box<dyn std::error::Error>>
+fn benchmark(input: &str, table: &[InstructionSpec], repeats: u32) -> Result<(), Box<dyn Error>>
+// scanner against the regex tokenizer, both have to see the same instructions
 {
-    // let re = Regex::new(r"(?<x>\d{1,3})")?;
-    // let string = "12|123|1234";
+    let scanner = Scanner::new(table)?;
+
+    let regex_before = Instant::now();
+    let mut regex_cnt = 0;
+    for _ in 0..repeats
+    {
+        regex_cnt = tokenize_regex(input, table)?.len();
+    }
+    let regex_duration = regex_before.elapsed() / repeats;
+
+    let scanner_before = Instant::now();
+    let mut scanner_cnt = 0;
+    for _ in 0..repeats
+    {
+        scanner_cnt = scanner.scan(input.as_bytes()).count();
+    }
+    let scanner_duration = scanner_before.elapsed() / repeats;
 
-    let re = Regex::new(r"(?<x>don't\(\))")?;
-    let string = "do()|123|don't()";
+    let stream_before = Instant::now();
+    for _ in 0..repeats
+    {
+        run_stream(input.as_bytes(), table)?;
+    }
+    let stream_duration = stream_before.elapsed() / repeats;
 
-    println!("{:?}", re.captures(string));
-    return Ok(());
+    let parallel_before = Instant::now();
+    for _ in 0..repeats
+    {
+        run_parallel(input.as_bytes(), table, PARALLEL_CHUNK_SIZE)?;
+    }
+    let parallel_duration = parallel_before.elapsed() / repeats;
+
+    println!("Instructions: regex {regex_cnt}, scanner {scanner_cnt}");
+    println!("Regex duration: {regex_duration:#?}");
+    println!("Scanner duration: {scanner_duration:#?}, speedup: {:.1}x",
+        regex_duration.div_duration_f32(scanner_duration));
+    println!("Stream duration: {stream_duration:#?}, speedup: {:.1}x",
+        regex_duration.div_duration_f32(stream_duration));
+    println!("Parallel duration: {parallel_duration:#?}, speedup: {:.1}x",
+        regex_duration.div_duration_f32(parallel_duration));
+
+    Ok(())
 }
 
 fn main() -> Result<(), Box<dyn Error>>
 {
-    let file_path = r"D:\src\Advent2024\inputs\03.txt";
-    let input = get_input(file_path)?;
+    let mut file_path = r"D:\src\Advent2024\inputs\03.txt".to_string();
+    let (mut bench, mut stream, mut parallel) = (false, false, false);
+    let (mut show_trace, mut show_annotated) = (false, false);
+    let mut table = default_instruction_set();
+    let mut max_digits: Option<usize> = None;
+
+    let mut args = std::env::args().skip(1);
+
+    while let Some(arg) = args.next()
+    {
+        match arg.as_str()
+        {
+            "--bench" => bench = true,
+            "--stream" => stream = true,
+            "--parallel" => parallel = true,
+            "--trace" => show_trace = true,
+            "--annotate" => show_annotated = true,
+            "--instructions" =>
+            {
+                let path = args.next().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput,
+                    "Missing instruction set file for --instructions"))?;
+                table = load_instruction_set(&path)?;
+            },
+            "--max-digits" =>
+            {
+                let digits = args.next().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput,
+                    "Missing number of digits for --max-digits"))?;
+                max_digits = Some(digits.parse()?);
+            },
+            _ if arg.starts_with("--") => return Err(Box::new(io::Error::new(io::ErrorKind::InvalidInput,
+                format!("Unknown option {arg}")))),
+            _ => file_path = arg,
+        }
+    }
+
+    // streaming and benchmark replace the whole run, nothing else would be done with them
+    let modes = [("--stream", stream), ("--bench", bench), ("--parallel", parallel),
+        ("--trace", show_trace), ("--annotate", show_annotated)];
+
+    for (mode, _) in modes.iter().take(2).filter(|(_, enabled)| *enabled)
+    {
+        let others: Vec<&str> = modes.iter().filter(|(name, enabled)| *enabled && name != mode).map(|(name, _)| *name).collect();
+
+        if !others.is_empty()
+        {
+            return Err(Box::new(io::Error::new(io::ErrorKind::InvalidInput,
+                format!("{mode} can't be combined with {}", others.join(", ")))));
+        }
+    }
+
+    if let Some(digits) = max_digits
+    {
+        // longer operands for every instruction, limits are checked with the rest of the set
+        for spec in table.iter_mut()
+        {
+            spec.max_digits = digits;
+        }
+    }
+
+    if stream
+    {
+        // whole input is not needed, it is scanned as it is read
+        let machine = run_stream(fs::File::open(&file_path)?, &table)?;
+        println!("Result: {}", machine.unconditional);
+        println!("Bonus result: {}", machine.accumulator);
+        return Ok(());
+    }
+
+    if bench
+    {
+        return benchmark(&get_input(&file_path)?, &table, 100);
+    }
+
+    // parallel run reads the file in batches, whole input is needed only by the rest
+    let input = match parallel && !show_trace && !show_annotated
+    {
+        true => None,
+        false => Some(get_input(&file_path)?),
+    };
+
+    if let Some(input) = input.as_ref().filter(|_| show_trace || show_annotated)
+    {
+        let entries = trace(input, &table)?;
+
+        if show_trace
+        {
+            print_trace(input, &entries);
+        }
+
+        if show_annotated
+        {
+            println!("{}", annotate(input, &entries));
+        }
+    }
+
+    let machine = match &input
+    {
+        Some(input) if !parallel => run(input, &table)?,
+        Some(input) => run_parallel(input.as_bytes(), &table, PARALLEL_CHUNK_SIZE)?,
+        None => run_parallel(fs::File::open(&file_path)?, &table, PARALLEL_CHUNK_SIZE)?,
+    };
+    println!("Result: {}", machine.unconditional);
+    println!("Bonus result: {}", machine.accumulator);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn compute(input: &str) -> Result<i128, Box<dyn Error>>
+    {
+        Ok(run(input, &default_instruction_set())?.unconditional)
+    }
+
+    fn compute_bonus(input: &str) -> Result<i128, Box<dyn Error>>
+    {
+        Ok(run(input, &default_instruction_set())?.accumulator)
+    }
+
+    const EXAMPLE: &str = "xmul(2,4)%&mul[3,7]!@^do_not_mul(5,5)+mul(32,64]then(mul(11,8)mul(8,5))";
+    const EXAMPLE_BONUS: &str = "xmul(2,4)&mul[3,7]!^don't()_mul(5,5)+mul(32,64](mul(11,8)undo()?mul(8,5))";
+
+    #[test]
+    fn example()
+    {
+        assert_eq!(compute(EXAMPLE).unwrap(), 161);
+    }
+
+    #[test]
+    fn example_bonus()
+    {
+        assert_eq!(compute_bonus(EXAMPLE_BONUS).unwrap(), 48);
+    }
+
+    #[test]
+    fn tokenize_example()
+    {
+        let table = default_instruction_set();
+        let scanner = Scanner::new(&table).unwrap();
+        let found: Vec<(&str, Vec<i64>, usize)> = scanner.scan(EXAMPLE_BONUS.as_bytes())
+            .map(|x| (x.spec.name.as_str(), x.operands().to_vec(), x.offset))
+            .collect();
+
+        assert_eq!(found, vec![
+            ("mul", vec![2, 4], 1),
+            ("don't", vec![], 20),
+            ("mul", vec![5, 5], 28),
+            ("mul", vec![11, 8], 48),
+            ("do", vec![], 59),
+            ("mul", vec![8, 5], 64),
+        ]);
+    }
+
+    #[test]
+    fn invalid_candidates()
+    {
+        // too many digits, wrong arity, operands for do
+        assert_eq!(compute("mul(1234,5)mul(1,2,3)mul(4)mul(,2)").unwrap(), 0);
+        assert_eq!(compute_bonus("do(1)don't(2)mul(3,4)").unwrap(), 12);
+        // candidate failing on its own doesn't hide the next one
+        assert_eq!(compute("mul(2,mul(3,4)").unwrap(), 12);
+    }
+
+    #[test]
+    fn custom_instruction()
+    {
+        let table = [
+            InstructionSpec::new("mul", 2, Effect::Product),
+            InstructionSpec::new("cube", 3, Effect::Product),
+            InstructionSpec::new("off", 0, Effect::Disable),
+        ];
+
+        let machine = run("cube(2,2,2)mul(3,3)off()mul(2,2)", &table).unwrap();
+        assert_eq!(machine.unconditional, 21);
+        assert_eq!(machine.accumulator, 17);
+        assert!(!machine.enabled);
+    }
+
+    fn summarize<'a>(instructions: impl Iterator<Item = Instruction<'a>>) -> Vec<(&'a str, Vec<i64>, usize, usize)>
+    {
+        instructions.map(|x| (x.spec.name.as_str(), x.operands().to_vec(), x.offset, x.len)).collect()
+    }
+
+    fn random_memory(len: usize, seed: u64) -> String
+    // mostly garbage made of instruction pieces, so candidates fail in every possible place
+    {
+        random_pieces(&["mul", "do", "don't", "(", ")", ",", "1", "23", "456", "7890", "m", "x", " ", "\n"], len, seed)
+    }
+
+    fn random_pieces(pieces: &[&str], len: usize, seed: u64) -> String
+    {
+        let mut state = seed;
+
+        (0..len).map(|_|
+            {
+                // xorshift
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                pieces[(state % pieces.len() as u64) as usize]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn scanner_matches_regex()
+    {
+        let table = default_instruction_set();
+        let scanner = Scanner::new(&table).unwrap();
+
+        for seed in 1..200
+        {
+            let memory = random_memory(500, seed);
+
+            assert_eq!(summarize(scanner.scan(memory.as_bytes())),
+                summarize(tokenize_regex(&memory, &table).unwrap().into_iter()),
+                "{memory}");
+        }
+    }
+
+    struct Trickle<'a>
+    // reader returning at most few bytes at once, every instruction straddles some boundary
+    {
+        data: &'a [u8],
+        step: usize,
+    }
+
+    impl Read for Trickle<'_>
+    {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize>
+        {
+            let len = self.step.min(buf.len()).min(self.data.len());
+            buf[..len].copy_from_slice(&self.data[..len]);
+            self.data = &self.data[len..];
+            Ok(len)
+        }
+    }
+
+    #[test]
+    fn stream_straddling_chunks()
+    {
+        let table = default_instruction_set();
+        let scanner = Scanner::new(&table).unwrap();
+
+        for seed in 1..50
+        {
+            let memory = random_memory(300, seed);
+            let expected = summarize(scanner.scan(memory.as_bytes()));
+
+            for step in [1, 2, 3, 7, 64]
+            {
+                let mut streamed = Vec::new();
+                scanner.scan_stream(Trickle { data: memory.as_bytes(), step }, |x| { streamed.push(*x); Ok(()) }).unwrap();
+                assert_eq!(summarize(streamed.into_iter()), expected, "{memory}, step {step}");
+            }
+        }
+    }
+
+    #[test]
+    fn stream_example()
+    {
+        let machine = run_stream(Trickle { data: EXAMPLE_BONUS.as_bytes(), step: 5 }, &default_instruction_set()).unwrap();
+        assert_eq!(machine.unconditional, 161);
+        assert_eq!(machine.accumulator, 48);
+    }
+
+    #[test]
+    fn trace_lines_and_columns()
+    {
+        let input = "mul(1,2)\nxdon't()mul(3,4)\n\u{e1}do()mul(5,6)";
+        let table = default_instruction_set();
+        let entries = trace(input, &table).unwrap();
+
+        let found: Vec<(usize, usize, usize, Option<i128>, bool, i128)> = entries.iter()
+            .map(|x| (x.instruction.offset, x.line, x.column, x.value, x.enabled, x.total))
+            .collect();
+
+        assert_eq!(found, vec![
+            (0, 1, 1, Some(2), true, 2),
+            (10, 2, 2, None, false, 2),
+            (17, 2, 9, Some(12), false, 2),
+            // two byte character before do(), column counts it once
+            (28, 3, 2, None, true, 2),
+            (32, 3, 6, Some(30), true, 32),
+        ]);
+    }
+
+    #[test]
+    fn annotate_disabled_region()
+    {
+        let input = "mul(1,2)_don't()?mul(3,4)!do()";
+        let table = default_instruction_set();
+        let entries = trace(input, &table).unwrap();
+
+        assert_eq!(annotate(input, &entries), format!(concat!(
+            "{c}mul(1,2){r}_{t}don't(){r}{d}?{r}{s}mul(3,4){r}{d}!{r}{t}do(){r}"),
+            c=COLOR_COUNTED, s=COLOR_SKIPPED, t=COLOR_TOGGLE, d=COLOR_DISABLED, r=COLOR_RESET));
+    }
+
+    const CUSTOM_SET: &str = r#"
+        [[instruction]]
+        name = "add"
+        arity = 3
+        max_digits = 4
+        effect = "sum"
+
+        [[instruction]]
+        name = "mul"
+        arity = 2
+        effect = "product"
+
+        [[instruction]]
+        name = "flip"
+        arity = 0
+        effect = "toggle"
+
+        [[instruction]]
+        name = "clr"
+        arity = 0
+        effect = "reset"
+    "#;
+
+    #[test]
+    fn custom_instruction_set()
+    {
+        let set: InstructionSet = toml::from_str(CUSTOM_SET).unwrap();
+        check_instruction_set(&set.instruction).unwrap();
+        assert_eq!(set.instruction[0].max_digits, 4);
+        assert_eq!(set.instruction[1].min_digits, 1);
+
+        let machine = run("add(1,20,3000)mul(2,3)flip()add(1,1,1)clr()mul(4,4)flip()mul(1,5)",
+            &set.instruction).unwrap();
+        assert_eq!(machine.unconditional, 16 + 5);
+        assert_eq!(machine.accumulator, 5);
+        assert!(machine.enabled);
+
+        // scanner and regex agree on custom sets too
+        let memory = "add(1,2)add(1,2,33333)add(1,2,3333)mul(1,2)clr(1)flip()";
+        let scanner = Scanner::new(&set.instruction).unwrap();
+        assert_eq!(summarize(scanner.scan(memory.as_bytes())),
+            summarize(tokenize_regex(memory, &set.instruction).unwrap().into_iter()));
+    }
+
+    #[test]
+    fn operand_digit_limits()
+    {
+        let table = [InstructionSpec { min_digits: 2, max_digits: 3, ..InstructionSpec::new("mul", 2, Effect::Product) }];
+        let machine = run("mul(1,10)mul(10,10)mul(100,1000)", &table).unwrap();
+        assert_eq!(machine.unconditional, 100);
+    }
+
+    #[test]
+    fn invalid_instruction_sets()
+    {
+        let invalid = [
+            InstructionSpec::new("", 0, Effect::Enable),
+            InstructionSpec::new("m(", 0, Effect::Enable),
+            InstructionSpec::new("mul", MAX_ARITY + 1, Effect::Product),
+            InstructionSpec { min_digits: 0, ..InstructionSpec::new("mul", 2, Effect::Product) },
+            InstructionSpec { max_digits: MAX_OPERAND_DIGITS + 1, ..InstructionSpec::new("mul", 2, Effect::Product) },
+        ];
+
+        for spec in invalid
+        {
+            assert!(Scanner::new(&[spec]).is_err());
+        }
+
+        assert!(check_instruction_set(&[InstructionSpec::new("do", 0, Effect::Enable),
+            InstructionSpec::new("do", 0, Effect::Disable)]).is_err());
+        assert!(toml::from_str::<InstructionSet>("[[instruction]]\nname = \"x\"\narity = 0\neffect = \"jump\"").is_err());
+    }
+
+    fn overflow_offset(result: Result<Machine, Box<dyn Error>>) -> usize
+    {
+        match *result.unwrap_err().downcast::<ComputeError>().unwrap()
+        {
+            ComputeError::Overflow { offset } => offset,
+        }
+    }
+
+    #[test]
+    fn wide_operands()
+    {
+        let mut table = default_instruction_set();
+        assert_eq!(run("mul(1234,1000)mul(2,3)", &table).unwrap().unconditional, 6);
+
+        table[0].max_digits = MAX_OPERAND_DIGITS;
+        let machine = run("mul(1234,1000)mul(999999999999999999,999999999999999999)", &table).unwrap();
+        assert_eq!(machine.unconditional, 1_234_000 + 999_999_999_999_999_999i128 * 999_999_999_999_999_999);
+        assert_eq!(run("mul(1,1234567890123456789)", &table).unwrap().unconditional, 0);
+    }
+
+    #[test]
+    fn overflow()
+    {
+        let table = [InstructionSpec { max_digits: MAX_OPERAND_DIGITS, ..InstructionSpec::new("mul", 3, Effect::Product) }];
+        let wide = "999999999999999999";
+
+        // single product too large
+        let input = format!("mul(1,2,3)xmul({wide},{wide},{wide})");
+        assert_eq!(overflow_offset(run(&input, &table)), 11);
+
+        // every product fits, their sum doesn't
+        let instruction = format!("mul({wide},{wide},1)");
+        let value = 999_999_999_999_999_999i128 * 999_999_999_999_999_999;
+        let fitting = (i128::MAX / value) as usize;
+        let input = instruction.repeat(fitting + 10);
+
+        assert_eq!(overflow_offset(run(&input, &table)), fitting * instruction.len());
+        assert_eq!(overflow_offset(run_stream(Trickle { data: input.as_bytes(), step: 7 }, &table)),
+            fitting * instruction.len());
+        assert!(trace(&input, &table).is_err());
+    }
+
+    fn assert_same_machine(input: &str, table: &[InstructionSpec], chunk_size: usize)
+    {
+        let expected = run(input, table).unwrap();
+        let machine = run_parallel(input.as_bytes(), table, chunk_size).unwrap();
+
+        assert_eq!((machine.enabled, machine.accumulator, machine.unconditional),
+            (expected.enabled, expected.accumulator, expected.unconditional), "{input}, chunks of {chunk_size}");
+    }
+
+    #[test]
+    fn parallel_matches_sequential()
+    {
+        let table = default_instruction_set();
+
+        for seed in 1..50
+        {
+            let memory = random_memory(400, seed);
+
+            for chunk_size in [1, 2, 5, 13, 64, 10_000]
+            {
+                assert_same_machine(&memory, &table, chunk_size);
+            }
+        }
+
+        assert_eq!(run_parallel(EXAMPLE_BONUS.as_bytes(), &table, 3).unwrap().accumulator, 48);
+    }
+
+    #[test]
+    fn parallel_custom_effects()
+    {
+        let set: InstructionSet = toml::from_str(CUSTOM_SET).unwrap();
+        let pieces = ["add", "mul", "flip", "clr", "(", ")", "()", ",", "1", "23", "456", "x"];
+
+        for seed in 1..50
+        {
+            let memory = random_pieces(&pieces, 400, seed);
+
+            for chunk_size in [1, 3, 8, 100]
+            {
+                assert_same_machine(&memory, &set.instruction, chunk_size);
+            }
+        }
+    }
+
+    #[test]
+    fn parallel_fallback()
+    {
+        // "do" inside "undo", a chunk starting at "do" can't know "undo(" was matched before
+        let table = [InstructionSpec::new("undo", 0, Effect::Disable), InstructionSpec::new("do", 0, Effect::Enable),
+            InstructionSpec::new("mul", 2, Effect::Product)];
+        assert!(!Scanner::new(&table).unwrap().is_chunkable());
+        assert!(Scanner::new(&default_instruction_set()).unwrap().is_chunkable());
+
+        assert_same_machine("undo()mul(2,3)undo()do()mul(4,5)", &table, 2);
+    }
+
+    #[test]
+    fn parallel_overflow()
+    {
+        let table = [InstructionSpec { max_digits: MAX_OPERAND_DIGITS, ..InstructionSpec::new("mul", 3, Effect::Product) }];
+        let instruction = "mul(999999999999999999,999999999999999999,1)";
+        let fitting = (i128::MAX / (999_999_999_999_999_999i128 * 999_999_999_999_999_999)) as usize;
+        let input = instruction.repeat(fitting + 10);
+
+        for chunk_size in [7, 100, 1000, input.len()]
+        {
+            assert_eq!(overflow_offset(run_parallel(input.as_bytes(), &table, chunk_size)), fitting * instruction.len());
+        }
+    }
+
+    #[test]
+    fn parallel_overflow_before_reset()
+    {
+        let set: InstructionSet = toml::from_str(CUSTOM_SET).unwrap();
+        let mut table = set.instruction;
+        table.iter_mut().for_each(|spec| spec.max_digits = MAX_OPERAND_DIGITS);
 
-    //_test_regex()?;
+        let wide = "999999999999999999";
+        let value = 999_999_999_999_999_999i128 * 999_999_999_999_999_999;
+        let fitting = (i128::MAX / value) as usize;
+        let instruction = format!("mul({wide},{wide})");
 
-    // let str = "mul(2,3)awdafawmmul[]mul9mul(4,1)".to_string();
-    let result = compute(&input)?;
-    println!("Result: {result}");
-    let bonus_result = compute_bonus(&input)?;
-    println!("Bonus result: {bonus_result}");
+        // last product before the reset overflows, the reset starts a new chunk right after it
+        let input = format!("{}{instruction}clr(){instruction}", instruction.repeat(fitting));
+        let offset = fitting * instruction.len();
+        assert_eq!(overflow_offset(run(&input, &table)), offset);
 
-    return Ok(());
-}
\ No newline at end of file
+        for chunk_size in [instruction.len(), offset, offset + instruction.len(), input.len()]
+        {
+            assert_eq!(overflow_offset(run_parallel(input.as_bytes(), &table, chunk_size)), offset, "chunks of {chunk_size}");
+        }
+    }
+
+    #[test]
+    fn parallel_batches()
+    {
+        let table = default_instruction_set();
+        // invalid UTF-8 between instructions
+        let mut input: Vec<u8> = Vec::new();
+        for seed in 1..20
+        {
+            input.extend(random_memory(200, seed).bytes());
+            input.extend([0xff, 0xc3, b'(']);
+        }
+        let expected = run_stream(input.as_slice(), &table).unwrap();
+
+        // batches of three chunks, read a few bytes at a time
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(3).build().unwrap();
+
+        for chunk_size in [1, 5, 13, 64]
+        {
+            let machine = pool.install(|| run_parallel(Trickle { data: &input, step: 7 }, &table, chunk_size).unwrap());
+            assert_eq!((machine.enabled, machine.accumulator, machine.unconditional),
+                (expected.enabled, expected.accumulator, expected.unconditional), "chunks of {chunk_size}");
+        }
+
+        // not chunkable, streamed instead
+        let table = [InstructionSpec::new("undo", 0, Effect::Disable), InstructionSpec::new("do", 0, Effect::Enable),
+            InstructionSpec::new("mul", 2, Effect::Product)];
+        let machine = run_parallel(&b"\xffundo()mul(2,3)do()mul(4,5)"[..], &table, 2).unwrap();
+        assert_eq!((machine.accumulator, machine.unconditional), (20, 26));
+    }
+}
@@ -1,5 +1,5 @@
-use std::{fs, io::{self, BufRead, Read}};
-use regex::Regex;
+use std::{collections::{HashMap, HashSet}, fs, io::{self, Read}, time::Instant};
+use unicode_segmentation::UnicodeSegmentation;
 
 fn get_input(file_path: &str) -> Result<String, io::Error>
 {
@@ -17,195 +17,704 @@ fn get_input(file_path: &str) -> Result<String, io::Error>
     let mut result: String = String::new();
     reader.read_to_string( &mut result)?;
     
-    return Ok(result);
+    Ok(result)
 }
 
-fn construct_common_regex(line_length: i32) -> String
-//  approach using one giant regex and or-ing occurences
-// doesn't work because this doesn't cover overlapping occurences
-// and different occurences can even start on the same position
+#[derive(Clone, Debug)]
+struct SearchOptions
 {
-    let partial_regexes = vec!["XMAS".to_string(), "SAMX".to_string(),
-    // vertical
-    format!("X.{{{l}}}M.{{{l}}}A.{{{l}}}S", l=line_length),
-    format!("S.{{{l}}}A.{{{l}}}M.{{{l}}}X", l=line_length),
-    // top left, bottom right
-    format!("X.{{{l}}}M.{{{l1}}}A.{{{l2}}}S", l=line_length + 1, l1=line_length + 2, 
-        l2=line_length + 3),
-    format!("S.{{{l}}}A.{{{l1}}}M.{{{l2}}}X", l=line_length + 1, l1=line_length + 2, 
-        l2=line_length + 3),
-    // top right, bottom left
-    format!("X.{{{l}}}M.{{{l1}}}A.{{{l2}}}S", l=line_length - 1, l1=line_length - 2, 
-        l2=line_length - 3),
-    format!("S.{{{l}}}A.{{{l1}}}M.{{{l2}}}X", l=line_length - 1, l1=line_length - 2, 
-        l2=line_length - 3)
-    ];
-
-    let mut result = String::new();
-    result.reserve(partial_regexes.iter().map(|x| x.len()).sum());
-
-    // (?ms) enables multiline  and matching \n with .
-    // TODO: why does right operand require a borrow operator here?
-    return partial_regexes.iter().fold(result, | acc: String, i: &String|
-        match acc
+    // words and templates continue on the opposite edge
+    wrap: bool,
+    // cells no match can use
+    mask: HashSet<(usize, usize)>,
+    // directions words are read in, templates are matched in all orientations
+    directions: Vec<(isize, isize)>,
+}
+
+impl Default for SearchOptions
+{
+    fn default() -> Self
+    {
+        SearchOptions { wrap: false, mask: HashSet::new(), directions: DIRECTIONS.to_vec() }
+    }
+}
+
+#[derive(Debug)]
+struct Grid
+{
+    // all rows have the same number of cells, cell is an index into symbols
+    rows: Vec<Vec<u32>>,
+    // distinct grapheme clusters of the grid, e.g. "e" and "e\u{301}" are different letters
+    symbols: Vec<String>,
+    ids: HashMap<String, u32>,
+    options: SearchOptions,
+}
+
+// (row, column) steps, clockwise from right
+const DIRECTIONS: [(isize, isize); 8] = [(0, 1), (1, 1), (1, 0), (1, -1), (0, -1), (-1, -1), (-1, 0), (-1, 1)];
+const DIRECTION_NAMES: [&str; 8] = ["right", "down-right", "down", "down-left", "left", "up-left", "up", "up-right"];
+
+fn direction_name(direction: (isize, isize)) -> &'static str
+{
+    DIRECTIONS.iter().position(|&x| x == direction).map_or("?", |idx| DIRECTION_NAMES[idx])
+}
+
+fn parse_directions(list: &str) -> Result<Vec<(isize, isize)>, io::Error>
+// comma separated names, e.g. "right,down,down-right"
+{
+    list.split(',')
+        .map(|name| DIRECTION_NAMES.iter().position(|&x| x == name.trim())
+            .map(|idx| DIRECTIONS[idx])
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput,
+                format!("Unknown direction \"{name}\", expected one of: {}", DIRECTION_NAMES.join(", ")))))
+        .collect()
+}
+
+fn parse_mask(input: &str) -> HashSet<(usize, usize)>
+// grid of the same shape, '#' marks a forbidden cell
+{
+    input.lines().enumerate()
+        .flat_map(|(row, line)| line.graphemes(true).enumerate().filter(|&(_, x)| x == "#").map(move |(col, _)| (row, col)))
+        .collect()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Match<'a>
+{
+    // first letter of the word, 0-based
+    row: usize,
+    col: usize,
+    direction: (isize, isize),
+    word: &'a str,
+}
+
+impl Match<'_>
+{
+    fn cells<'g>(&'g self, grid: &'g Grid) -> impl Iterator<Item = (usize, usize)> + 'g
+    {
+        (0..self.word.graphemes(true).count() as isize).filter_map(|idx|
+            grid.position(self.row as isize + self.direction.0 * idx, self.col as isize + self.direction.1 * idx))
+    }
+}
+
+// ANSI colours for the rendered grid
+const COLOR_MATCH: &str = "\x1b[1;32m";
+const COLOR_OTHER: &str = "\x1b[2m";
+const COLOR_RESET: &str = "\x1b[0m";
+
+impl Grid
+{
+    fn parse(input: &str) -> Result<Grid, io::Error>
+    // one row per line, LF or CRLF, one cell per grapheme cluster
+    {
+        let mut grid = Grid { rows: Vec::new(), symbols: Vec::new(), ids: HashMap::new(), options: SearchOptions::default() };
+        // ids of ASCII letters without hashing, those are almost all grids
+        let mut ascii = [u32::MAX; 128];
+
+        for (idx, line) in input.trim_end_matches(['\r', '\n']).lines().enumerate()
+        {
+            let row: Vec<u32> = match line.is_ascii()
+            {
+                // every ASCII character is a grapheme of its own, CR LF is already cut off
+                true => line.bytes().map(|x|
+                    {
+                        if ascii[x as usize] == u32::MAX
+                        {
+                            ascii[x as usize] = grid.intern(&(x as char).to_string());
+                        }
+                        ascii[x as usize]
+                    }).collect(),
+                false => line.graphemes(true).map(|x| grid.intern(x)).collect(),
+            };
+
+            if let Some(first) = grid.rows.first().filter(|first| first.len() != row.len())
+            {
+                return Err(io::Error::new(io::ErrorKind::InvalidData,
+                    format!("Row {} has {} cells, first row has {}", idx + 1, row.len(), first.len())));
+            }
+            grid.rows.push(row);
+        }
+
+        Ok(grid)
+    }
+
+    fn intern(&mut self, symbol: &str) -> u32
+    {
+        if let Some(&id) = self.ids.get(symbol)
+        {
+            return id;
+        }
+
+        let id = self.symbols.len() as u32;
+        self.symbols.push(symbol.to_string());
+        self.ids.insert(symbol.to_string(), id);
+        id
+    }
+
+    fn word_ids(&self, word: &str) -> Vec<u32>
+    // word as grid symbols, empty if it has a letter the grid doesn't, so it can't be found
+    {
+        word.graphemes(true).map(|x| self.ids.get(x).copied()).collect::<Option<Vec<u32>>>().unwrap_or_default()
+    }
+
+    fn position(&self, row: isize, col: isize) -> Option<(usize, usize)>
+    // cell the coordinates refer to, taken modulo the grid size in wrap-around mode,
+    // None outside of the grid and on masked cells
+    {
+        let (row, col) = match self.options.wrap
+        {
+            true if self.rows.is_empty() => return None,
+            true =>
+            {
+                let row = row.rem_euclid(self.rows.len() as isize) as usize;
+                let width = self.rows[row].len() as isize;
+
+                if width == 0
+                {
+                    return None;
+                }
+                (row, col.rem_euclid(width) as usize)
+            },
+            false if row < 0 || col < 0 => return None,
+            false => (row as usize, col as usize),
+        };
+
+        let inside = col < self.rows.get(row)?.len();
+        (inside && !self.options.mask.contains(&(row, col))).then_some((row, col))
+    }
+
+    fn get(&self, row: isize, col: isize) -> Option<u32>
+    {
+        let (row, col) = self.position(row, col)?;
+        Some(self.rows[row][col])
+    }
+
+    fn matches_at(&self, word: &[u32], row: usize, col: usize, direction: (isize, isize)) -> bool
+    // word read from the cell in the direction, running off the grid is a mismatch
+    {
+        word.iter().enumerate().all(|(idx, &letter)|
+            self.get(row as isize + direction.0 * idx as isize, col as isize + direction.1 * idx as isize) == Some(letter))
+    }
+
+    fn count_word(&self, word: &str) -> usize
+    // occurrences in all 8 directions, can overlap and share starting cell
+    {
+        self.count_words(&[word])[0]
+    }
+
+    fn count_words(&self, words: &[&str]) -> Vec<usize>
+    // occurrences of each word
+    {
+        let mut counts = vec![0; words.len()];
+        self.walk_words(words, |idx, _, _, _| counts[idx] += 1);
+        counts
+    }
+
+    fn find_words<'a>(&self, words: &[&'a str]) -> Vec<Match<'a>>
+    // every occurrence, in order of starting cell
+    {
+        let mut found: Vec<Match> = Vec::new();
+        self.walk_words(words, |idx, row, col, direction| found.push(Match { row, col, direction, word: words[idx] }));
+        found
+    }
+
+    fn walk_words(&self, words: &[&str], mut found: impl FnMut(usize, usize, usize, (isize, isize)))
+    // calls found with word index, row, column and direction of each occurrence,
+    // the grid is walked once for the whole list
+    {
+        let words: Vec<Vec<u32>> = words.iter().map(|word| self.word_ids(word)).collect();
+        let directions: Vec<Vec<(isize, isize)>> = words.iter().map(|word| self.directions_for(word)).collect();
+
+        // words by their first letter, only those are tried from a cell
+        let mut by_first: HashMap<u32, Vec<usize>> = HashMap::new();
+        for (idx, word) in words.iter().enumerate().filter(|(_, word)| !word.is_empty())
+        {
+            by_first.entry(word[0]).or_default().push(idx);
+        }
+
+        for (row, cells) in self.rows.iter().enumerate()
+        {
+            for (col, cell) in cells.iter().enumerate()
+            {
+                for &idx in by_first.get(cell).into_iter().flatten()
+                {
+                    for &direction in &directions[idx]
+                    {
+                        if self.matches_at(&words[idx], row, col, direction)
+                        {
+                            found(idx, row, col, direction);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn render(&self, highlighted: &HashSet<(usize, usize)>, color: bool) -> String
+    // letters not covered by any match are replaced by dots, or dimmed when coloured
+    {
+        let mut rendered = String::new();
+
+        for (row, cells) in self.rows.iter().enumerate()
         {
-            s if s.is_empty() => "(?ms)".to_string() + i, // first iterated member
-            _ => acc + "|" + i,
-        });
+            for (col, &cell) in cells.iter().enumerate()
+            {
+                let cell = &self.symbols[cell as usize];
+
+                match (highlighted.contains(&(row, col)), color)
+                {
+                    (true, true) => rendered.push_str(&format!("{COLOR_MATCH}{cell}{COLOR_RESET}")),
+                    (true, false) => rendered.push_str(cell),
+                    (false, true) => rendered.push_str(&format!("{COLOR_OTHER}{cell}{COLOR_RESET}")),
+                    (false, false) => rendered.push('.'),
+                }
+            }
+            rendered.push('\n');
+        }
+
+        rendered
+    }
+
+    fn directions_for(&self, word: &[u32]) -> Vec<(isize, isize)>
+    // palindrome read backwards covers the same cells, single letter is the same in every direction
+    {
+        let allowed = &self.options.directions;
+
+        match word.len()
+        {
+            1 => allowed.iter().take(1).copied().collect(),
+            _ if word.iter().eq(word.iter().rev()) => allowed.iter().enumerate()
+                .filter(|&(idx, &(dr, dc))| !allowed[..idx].contains(&(-dr, -dc)))
+                .map(|(_, &direction)| direction)
+                .collect(),
+            _ => allowed.clone(),
+        }
+    }
+}
+
+struct LetterBitmaps
+// for every letter one bitmap per row, bit c of row r is set when cell (r, c) holds the letter
+{
+    // u64 words per bitmap
+    stride: usize,
+    // bitmap index of every grid symbol, usize::MAX for symbols not searched for
+    indices: Vec<usize>,
+    letter_cnt: usize,
+    // row by row, bitmaps of all letters of one row next to each other
+    bits: Vec<u64>,
 }
 
-fn construct_regex(input: &String) -> Regex
+impl LetterBitmaps
 {
-    // when last command has no semicolon, it is returned??
-    match Regex::new(format!("(?ms){}", input).as_str())
+    fn new(grid: &Grid, letters: impl Iterator<Item = u32>) -> Self
+    // masked cells hold no letter, rows are filled in parallel
     {
-        Ok(x) => x,
-        Err(e) => panic!("Could not create regex \"{}\": {}", input, e)
+        use rayon::iter::{IndexedParallelIterator, ParallelIterator};
+        use rayon::slice::ParallelSliceMut;
+
+        let stride = grid.rows.iter().map(|row| row.len()).max().unwrap_or(0).div_ceil(64);
+        let mut indices = vec![usize::MAX; grid.symbols.len()];
+        let mut letter_cnt = 0;
+        for letter in letters
+        {
+            if indices[letter as usize] == usize::MAX
+            {
+                indices[letter as usize] = letter_cnt;
+                letter_cnt += 1;
+            }
+        }
+
+        let row_len = (stride * letter_cnt).max(1);
+        let mut bits = vec![0u64; row_len * grid.rows.len()];
+
+        let index_of = |cell: u32| Some(indices[cell as usize]).filter(|&idx| idx != usize::MAX);
+        let masked = |row, col| !grid.options.mask.is_empty() && grid.options.mask.contains(&(row, col));
+
+        bits.par_chunks_mut(row_len).enumerate().for_each(|(row, bitmaps)|
+            for (col, &cell) in grid.rows[row].iter().enumerate()
+            {
+                if let Some(idx) = index_of(cell).filter(|_| !masked(row, col))
+                {
+                    bitmaps[idx * stride + col / 64] |= 1 << (col % 64);
+                }
+            });
+
+        LetterBitmaps { stride, indices, letter_cnt, bits }
+    }
+
+    fn row(&self, letter: u32, row: usize) -> &[u64]
+    {
+        let start = (row * self.letter_cnt + self.indices[letter as usize]) * self.stride;
+        &self.bits[start..start + self.stride]
     }
 }
 
-fn construct_regex_base(line_length: i32) -> Vec<regex::Regex>
-// use different regex for each pattern
+fn bits_at(row: &[u64], offset: isize) -> u64
+// 64 bits of the row starting at column offset, columns outside of the row are zero
 {
-    let partial_regexes: Vec<String> = vec![
-        "XMAS".to_string(), "SAMX".to_string(),
-        // vertical
-        format!("X.{{{l}}}M.{{{l}}}A.{{{l}}}S", l=line_length),
-        format!("S.{{{l}}}A.{{{l}}}M.{{{l}}}X", l=line_length),
-        // top left, bottom right
-        format!("X.{{{l}}}M.{{{l}}}A.{{{l}}}S", l=line_length + 1),
-        format!("S.{{{l}}}A.{{{l}}}M.{{{l}}}X", l=line_length + 1),
-        // top right, bottom left
-        format!("X.{{{l}}}M.{{{l}}}A.{{{l}}}S", l=line_length - 1),
-        format!("S.{{{l}}}A.{{{l}}}M.{{{l}}}X", l=line_length - 1),
-    ];
-
-    return partial_regexes.iter()
-        .map(construct_regex)
-        .collect();
+    let get = |idx: isize| if idx >= 0 && (idx as usize) < row.len() { row[idx as usize] } else { 0 };
+    let (word, bit) = (offset.div_euclid(64), offset.rem_euclid(64));
+
+    match bit
+    {
+        0 => get(word),
+        _ => (get(word) >> bit) | (get(word + 1) << (64 - bit)),
+    }
 }
 
-fn construct_regex_bonus(line_length: i32) -> Vec<regex::Regex>
+impl Grid
 {
-    let partial_regexes: Vec<String> = vec![
-        format!("M.M.{{{l1}}}A.{{{l2}}}S.S", l1=line_length - 1, l2=line_length - 1),
-        format!("M.S.{{{l1}}}A.{{{l2}}}M.S", l1=line_length - 1, l2=line_length - 1),
-        format!("M.S.{{{l1}}}A.{{{l2}}}S.M", l1=line_length - 1, l2=line_length - 1),
-        format!("S.M.{{{l1}}}A.{{{l2}}}M.S", l1=line_length - 1, l2=line_length - 1),
-        format!("S.M.{{{l1}}}A.{{{l2}}}S.M", l1=line_length - 1, l2=line_length - 1),
-        format!("S.S.{{{l1}}}A.{{{l2}}}M.M", l1=line_length - 1, l2=line_length - 1),
-    ];
-
-    return partial_regexes.iter()
-        .map(construct_regex)
-        .collect();
+    fn count_words_bitwise(&self, words: &[&str]) -> Vec<usize>
+    // same as count_words, but 64 starting cells are checked at once: the bitmap of the first letter
+    // is ANDed with bitmaps of following letters from rows below or above, shifted sideways,
+    // rows are processed in parallel, wrap-around falls back to walking the grid
+    {
+        use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+        if self.options.wrap
+        {
+            return self.count_words(words);
+        }
+
+        let words: Vec<Vec<u32>> = words.iter().map(|word| self.word_ids(word)).collect();
+        let directions: Vec<Vec<(isize, isize)>> = words.iter().map(|word| self.directions_for(word)).collect();
+        let bitmaps = LetterBitmaps::new(self, words.iter().flatten().copied());
+        let height = self.rows.len() as isize;
+
+        (0..self.rows.len()).into_par_iter()
+            .map_init(|| vec![0u64; bitmaps.stride], |matches, row|
+            {
+                let mut counts = vec![0; words.len()];
+
+                for (idx, word) in words.iter().enumerate().filter(|(_, word)| !word.is_empty())
+                {
+                    for &(dr, dc) in &directions[idx]
+                    {
+                        matches.copy_from_slice(bitmaps.row(word[0], row));
+
+                        for (step, &letter) in word.iter().enumerate().skip(1)
+                        {
+                            let target = row as isize + dr * step as isize;
+
+                            if target < 0 || target >= height
+                            {
+                                matches.fill(0);
+                                break;
+                            }
+
+                            let letters = bitmaps.row(letter, target as usize);
+                            for (w, x) in matches.iter_mut().enumerate()
+                            {
+                                *x &= bits_at(letters, (w * 64) as isize + dc * step as isize);
+                            }
+                        }
+
+                        counts[idx] += matches.iter().map(|x| x.count_ones() as usize).sum::<usize>();
+                    }
+                }
+
+                counts
+            })
+            .reduce(|| vec![0; words.len()], |a, b| a.iter().zip(b).map(|(x, y)| x + y).collect())
+    }
 }
 
-fn get_line_length(input: &String) -> i32
+fn generate_grid(width: usize, height: usize, seed: u64) -> String
+// random letters of XMAS, xorshift so that benchmark runs are comparable
 {
-    let mut idx: i32 = 0;
-    input.chars().find(|&x| 
-        match x == '\n'
+    let letters = ['X', 'M', 'A', 'S'];
+    let mut state = seed;
+    let mut grid = String::with_capacity((width + 1) * height);
+
+    for _ in 0..height
+    {
+        for _ in 0..width
         {
-            true => { return true; },
-            false => { idx += 1; return false; } ,
-        });
-    return idx;
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            grid.push(letters[(state % letters.len() as u64) as usize]);
+        }
+        grid.push('\n');
+    }
+
+    grid
+}
+
+fn benchmark(size: usize) -> Result<(), Box<dyn std::error::Error>>
+// cell walk against bitmaps on a generated square grid
+{
+    let generate_before = Instant::now();
+    let grid = Grid::parse(&generate_grid(size, size, 42))?;
+    println!("Generated {size}x{size} grid in {:#?}", generate_before.elapsed());
+
+    let walk_before = Instant::now();
+    let walk_cnt = grid.count_word("XMAS");
+    let walk_duration = walk_before.elapsed();
+
+    let bitwise_before = Instant::now();
+    let bitwise_cnt = grid.count_words_bitwise(&["XMAS"])[0];
+    let bitwise_duration = bitwise_before.elapsed();
+
+    println!("Occurences: walk {walk_cnt}, bitwise {bitwise_cnt}");
+    println!("Walk duration: {walk_duration:#?}");
+    println!("Bitwise duration: {bitwise_duration:#?}, speedup: {:.1}x",
+        walk_duration.div_duration_f32(bitwise_duration));
+
+    Ok(())
+}
+
+// side of the generated grid for --bench
+const BENCH_SIZE: usize = 10_000;
+
+#[derive(Clone, Debug, PartialEq)]
+struct Template
+// small 2D pattern, None cells match anything
+{
+    cells: Vec<Vec<Option<String>>>,
 }
 
-fn find_regexes(input: &String, regexes: &Vec<Regex>, line_length: i32) -> Result<i32, Box<dyn std::error::Error>>
+// template cell matching any letter
+const WILDCARD: &str = ".";
+
+impl Template
 {
-    let mut count = 0;
-    let mut idx = 0;
-
-    // doesn't work because overlapping regexes won't be found
-    // for capture in re.captures_iter(&input)
-    // {
-    //     count += 1
-    //     println!("{:?}",capture.get(0))
-    // }
-
-    // 
-    // still doesn't work, won't find different occurences starting on the same char
-    // while true
-    // {
-    //     match re.find_at(&input, idx)
-    //     {
-    //         Some(found) => 
-    //         {
-    //             idx = found.start() + 1;
-    //             count += 1;
-    //         }
-    //         None => { break; }
-    //     }
-    // }
-
-    // way to go, iterate over all regexes one at a time
-    for reg in regexes
-    {
-        idx = 0;
-        //println!("Regex: {}", reg);
-
-        loop
+    fn parse(pattern: &str) -> Result<Template, io::Error>
+    // rows separated by new lines or slashes, e.g. "M.S/.A./M.S"
+    {
+        let cells: Vec<Vec<Option<String>>> = pattern.split(['\n', '/'])
+            .map(|row| row.trim())
+            .filter(|row| !row.is_empty())
+            .map(|row| row.graphemes(true).map(|x| if x == WILDCARD { None } else { Some(x.to_string()) }).collect())
+            .collect();
+
+        if cells.is_empty() || cells.iter().any(|row| row.len() != cells[0].len())
         {
-            match reg.find_at(&input, idx)
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                format!("Template has to be a non-empty rectangle: {pattern}")));
+        }
+
+        Ok(Template { cells })
+    }
+
+    fn rotated(&self) -> Template
+    // clockwise by 90 degrees
+    {
+        let height = self.cells.len();
+        let width = self.cells[0].len();
+
+        Template { cells: (0..width).map(|row| (0..height).map(|col| self.cells[height - 1 - col][row].clone()).collect()).collect() }
+    }
+
+    fn reflected(&self) -> Template
+    // mirrored left to right
+    {
+        Template { cells: self.cells.iter().map(|row| row.iter().rev().cloned().collect()).collect() }
+    }
+
+    fn cells<'g>(&'g self, grid: &'g Grid, row: usize, col: usize) -> impl Iterator<Item = (usize, usize)> + 'g
+    // grid cells of letters, not wildcards, with the top left corner on the cell
+    {
+        self.cells.iter().enumerate().flat_map(move |(dr, cells)|
+            cells.iter().enumerate().filter(|(_, cell)| cell.is_some())
+                .filter_map(move |(dc, _)| grid.position((row + dr) as isize, (col + dc) as isize)))
+    }
+
+    fn variants(&self) -> Vec<Template>
+    // all rotations and reflections, symmetric ones only once
+    {
+        let mut variants: Vec<Template> = Vec::new();
+        let mut current = self.clone();
+
+        for _ in 0..4
+        {
+            for variant in [current.reflected(), current.clone()]
             {
-                Some(found) => 
+                if !variants.contains(&variant)
                 {
-                    idx = found.start() + 1;
-                    count += 1;
-                    // println!("Line {}, char {}: {}", 
-                    //     (found.start() as i32) / line_length,
-                    //     (found.start() as i32) % line_length,
-                    //     found.as_str());//.replace("\n", "|")
+                    variants.push(variant);
                 }
-                None => { break; }
             }
+            current = current.rotated();
         }
+
+        variants
     }
-    
-    return Ok(count);
 }
 
-fn find_xmas(input: &String) -> Result<i32, Box<dyn std::error::Error>>
+impl Grid
 {
-    let line_length = get_line_length(&input);
-    println!("Line length: {}", &line_length);
+    fn matches_template(&self, template: &Template, row: usize, col: usize) -> bool
+    // template placed with its top left corner on the cell
+    {
+        template.cells.iter().enumerate().all(|(dr, cells)|
+            cells.iter().enumerate().all(|(dc, cell)|
+            {
+                let found = self.get((row + dr) as isize, (col + dc) as isize);
+
+                match cell
+                {
+                    None => found.is_some(),
+                    Some(letter) => found.is_some_and(|id| self.symbols[id as usize] == *letter),
+                }
+            }))
+    }
 
-    let regexes = construct_regex_base(line_length);
-    return find_regexes(input, &regexes, line_length);
+    fn count_template(&self, template: &Template) -> usize
+    // matches of every rotation and reflection
+    {
+        self.find_template(template).len()
+    }
+
+    fn find_template(&self, template: &Template) -> Vec<(usize, usize, Template)>
+    // top left corner and the rotated or reflected variant of each match
+    {
+        let variants = template.variants();
+
+        self.rows.iter().enumerate()
+            .flat_map(|(row, cells)| (0..cells.len()).map(move |col| (row, col)))
+            .flat_map(|(row, col)| variants.iter()
+                .filter(move |variant| self.matches_template(variant, row, col))
+                .map(move |variant| (row, col, variant.clone())))
+            .collect()
+    }
 }
 
-fn find_xmas_bonus(input: &String) -> Result<i32, Box<dyn std::error::Error>>
+fn load_words(file_path: &str) -> Result<Vec<String>, io::Error>
+// one word per line, empty lines are skipped
 {
-    let line_length = get_line_length(&input);
-    println!("Line length: {}", &line_length);
-
-    let regexes = construct_regex_bonus(line_length);
-    return find_regexes(input, &regexes, line_length);
+    Ok(get_input(file_path)?.lines()
+        .map(|line| line.trim().to_string())
+        .filter(|word| !word.is_empty())
+        .collect())
 }
 
-fn main()
+// two MAS crossing diagonally, in any orientation
+const X_MAS: &str = "M.S/.A./M.S";
+
+fn main() -> Result<(), Box<dyn std::error::Error>>
 {
-    let file_path: &str = r"D:\src\Advent2024\inputs\042.txt";
+    let mut file_path = r"D:\src\Advent2024\inputs\042.txt".to_string();
+    let mut words: Vec<String> = Vec::new();
+    let mut templates: Vec<Template> = Vec::new();
+    let (mut show_matches, mut render, mut color, mut bench) = (false, false, false, false);
+    let mut options = SearchOptions::default();
+    // options and the grid path, values of options not counted
+    let mut arg_cnt = 0;
+
+    let mut args = std::env::args().skip(1);
 
-    let input: String = match get_input(file_path)
+    while let Some(arg) = args.next()
     {
-        Ok(s) => s,
-        Err(e) => panic!("File not found: {e:?}")
-    };
+        arg_cnt += 1;
 
-    // match find_xmas(input)
-    match find_xmas_bonus(&input)
-    // match find_xmas("Sawd\naAaw\nfeMg\nawdX\n".to_string())
-    // match find_xmas("awdS\naaAw\nfMeg\nXawd\n".to_string()) // diagonal tr bl
-    // match find_xmas("Saaa\nAaaa\nMaaa\nXaaa\n".to_string()) // vertical
-    // match find_xmas_bonus("MaMa\nwAaa\nSaSa\nwaaa\n".to_string()) // vertical
+        match arg.as_str()
+        {
+            "--word" => words.push(args.next().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput,
+                "Missing word for --word"))?),
+            "--words" =>
+            {
+                let path = args.next().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput,
+                    "Missing word list file for --words"))?;
+                words.extend(load_words(&path)?);
+            },
+            "--template" =>
+            {
+                let pattern = args.next().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput,
+                    "Missing pattern for --template"))?;
+                templates.push(Template::parse(&pattern)?);
+            },
+            "--wrap" => options.wrap = true,
+            "--mask" =>
+            {
+                let path = args.next().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput,
+                    "Missing mask file for --mask"))?;
+                options.mask = parse_mask(&get_input(&path)?);
+            },
+            "--directions" =>
+            {
+                let list = args.next().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput,
+                    "Missing direction list for --directions"))?;
+                options.directions = parse_directions(&list)?;
+            },
+            "--matches" => show_matches = true,
+            "--render" => render = true,
+            "--color" => { render = true; color = true },
+            "--bench" => bench = true,
+            _ if arg.starts_with("--") => return Err(Box::new(io::Error::new(io::ErrorKind::InvalidInput,
+                format!("Unknown option {arg}")))),
+            _ => file_path = arg,
+        }
+    }
+
+    if bench
     {
-        Ok(i) => println!("Found {} occurences", i),
-        Err(e) => panic!("Search for xmas failed: {e:?}")
-    };
+        if arg_cnt > 1
+        {
+            return Err(Box::new(io::Error::new(io::ErrorKind::InvalidInput,
+                "--bench generates its own grid and can't be combined with other arguments")));
+        }
+        return benchmark(BENCH_SIZE);
+    }
+
+    let grid = Grid { options, ..Grid::parse(&get_input(&file_path)?)? };
 
+    if (show_matches || render) && words.is_empty() && templates.is_empty()
+    {
+        words.push("XMAS".to_string());
+    }
+
+    if !words.is_empty() || !templates.is_empty()
+    {
+        let words: Vec<&str> = words.iter().map(|x| x.as_str()).collect();
+        let mut highlighted: HashSet<(usize, usize)> = HashSet::new();
+
+        let found = grid.find_words(&words);
+        highlighted.extend(found.iter().flat_map(|x| x.cells(&grid)));
+
+        if show_matches
+        {
+            // 1-based like in an editor
+            for x in &found
+            {
+                println!("{:>5}:{:<5} {:<10} {}", x.row + 1, x.col + 1, direction_name(x.direction), x.word);
+            }
+        }
+
+        for (word, count) in words.iter().zip(grid.count_words_bitwise(&words))
+        {
+            println!("{word}: {count}");
+        }
+
+        for template in &templates
+        {
+            let rows: Vec<String> = template.cells.iter()
+                .map(|row| row.iter().map(|x| x.as_deref().unwrap_or(WILDCARD)).collect())
+                .collect();
+            let found = grid.find_template(template);
+            highlighted.extend(found.iter().flat_map(|(row, col, variant)| variant.cells(&grid, *row, *col)));
+
+            if show_matches
+            {
+                for (row, col, _) in &found
+                {
+                    println!("{:>5}:{:<5} {}", row + 1, col + 1, rows.join("/"));
+                }
+            }
+            println!("{}: {}", rows.join("/"), found.len());
+        }
+
+        if render
+        {
+            print!("{}", grid.render(&highlighted, color));
+        }
+        return Ok(());
+    }
+
+    println!("Found {} occurences", grid.count_words_bitwise(&["XMAS"])[0]);
+    println!("Found {} X-MAS occurences", grid.count_template(&Template::parse(X_MAS)?));
+
+    Ok(())
 }
 
 #[cfg(test)]
@@ -213,67 +722,77 @@ mod tests
 {
     use super::*;
 
+    fn find_xmas(input: &str) -> Result<i32, Box<dyn std::error::Error>>
+    {
+        Ok(Grid::parse(input)?.count_word("XMAS") as i32)
+    }
+
+    fn find_xmas_bonus(input: &str) -> Result<i32, Box<dyn std::error::Error>>
+    {
+        Ok(Grid::parse(input)?.count_template(&Template::parse(X_MAS)?) as i32)
+    }
+
     // TODO: for the love of god, find a way to parametrize tests
     #[test]
     fn horizontal() 
     {
-        let result: i32 = find_xmas(&"XMAS".to_string()).unwrap_or(0);
+        let result: i32 = find_xmas("XMAS").unwrap_or(0);
         assert_eq!(result, 1);
     }
 
     #[test]
     fn horizontal_bw() 
     {
-        let result: i32 = find_xmas(&"SAMX".to_string()).unwrap_or(0);
+        let result: i32 = find_xmas("SAMX").unwrap_or(0);
         assert_eq!(result, 1);
     }
 
     #[test]
     fn vertical() 
     {
-        let result: i32 = find_xmas(&"Xawd\nMwaw\nAddg\nSawd\n".to_string()).unwrap_or(0);
+        let result: i32 = find_xmas("Xawd\nMwaw\nAddg\nSawd\n").unwrap_or(0);
         assert_eq!(result, 1);
     }
     
     #[test]
     fn vertical_bw() 
     {
-        let result: i32 = find_xmas(&"Sawd\nAwaw\nMfeg\nXawd\n".to_string()).unwrap_or(0);
+        let result: i32 = find_xmas("Sawd\nAwaw\nMfeg\nXawd\n").unwrap_or(0);
         assert_eq!(result, 1);
     }
 
     #[test]
     fn tlbr() 
     {
-        let result: i32 = find_xmas(&"Xawd\naMaw\nfeAg\nawdS\n".to_string()).unwrap_or(0);
+        let result: i32 = find_xmas("Xawd\naMaw\nfeAg\nawdS\n").unwrap_or(0);
         assert_eq!(result, 1);
     }
 
     #[test]
     fn tlbr_bw()
     {
-        let result: i32 = find_xmas(&"Sawd\naAaw\nfeMg\nawdX\n".to_string()).unwrap_or(0);
+        let result: i32 = find_xmas("Sawd\naAaw\nfeMg\nawdX\n").unwrap_or(0);
         assert_eq!(result, 1);
     }
 
     #[test]
     fn trbl()
     {
-        let result: i32 = find_xmas(&"awdX\naaMw\nfAeg\nSawd\n".to_string()).unwrap_or(0);
+        let result: i32 = find_xmas("awdX\naaMw\nfAeg\nSawd\n").unwrap_or(0);
         assert_eq!(result, 1);
     }
 
     #[test]
     fn trbl_bw()
     {
-        let result: i32 = find_xmas(&"awdS\naaAw\nfMeg\nXawd\n".to_string()).unwrap_or(0);
+        let result: i32 = find_xmas("awdS\naaAw\nfMeg\nXawd\n").unwrap_or(0);
         assert_eq!(result, 1);
     }
 
     #[test]
     fn bonus()
     {
-        let inputs = vec![
+        let inputs = [
             "MaMa\nwAaa\nSaSa\nwaaa\n".to_string(),
             "MaSa\nwAaa\nMaSa\nwaaa\n".to_string(),
             "MaSa\nwAaa\nSaMa\nwaaa\n".to_string(),
@@ -282,6 +801,9 @@ mod tests
             "SaSa\nwAaa\nMaMa\nwaaa\n".to_string(),
         ];
 
+        // both diagonals have to read MAS, third and fourth have M and S on the same diagonal
+        let expected = vec![1, 1, 0, 0, 1, 1];
+
         assert_eq!(
             inputs.iter()
                 .map(|input: &String| -> i32
@@ -291,7 +813,230 @@ mod tests
                             Err(e) => panic!("{e}"),
                         }    
                     })
-                .sum::<i32>(), 
-            inputs.len() as i32);
+                .collect::<Vec<i32>>(), 
+            expected);
+    }
+
+    const EXAMPLE: &str = "MMMSXXMASM
+MSAMXMSMSA
+AMXSXMAAMM
+MSAMASMSMX
+XMASAMXAMM
+XXAMMXXAMA
+SMSMSASXSS
+SAXAMASAAA
+MAMMMXMMMM
+MXMXAXMASX
+";
+
+    #[test]
+    fn example()
+    {
+        assert_eq!(find_xmas(EXAMPLE).unwrap(), 18);
+        assert_eq!(find_xmas_bonus(EXAMPLE).unwrap(), 9);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn no_wrap_around()
+    {
+        // words must not continue on the next row
+        assert_eq!(find_xmas("aaXM\nASaa\naaaa").unwrap(), 0);
+        assert_eq!(find_xmas("aaX\nMaa\nAaa\nSaa").unwrap(), 0);
+        assert_eq!(find_xmas("X\nM\nA\nS").unwrap(), 1);
+        assert_eq!(find_xmas("XMASAMX").unwrap(), 2);
+    }
+
+    #[test]
+    fn word_list()
+    {
+        let grid = Grid::parse(EXAMPLE).unwrap();
+        assert_eq!(grid.count_words(&["XMAS", "SAMX", "MAS", "", "QQ"]), vec![18, 18, 38, 0, 0]);
+    }
+
+    #[test]
+    fn palindromes_and_letters()
+    {
+        let grid = Grid::parse("ABA\nBBB\nABA").unwrap();
+        // each placement counted once, not once per reading direction
+        assert_eq!(grid.count_words(&["ABA", "A", "B", "AB"]), vec![6, 4, 5, 12]);
+    }
+
+    #[test]
+    fn template_variants()
+    {
+        assert_eq!(Template::parse(X_MAS).unwrap().variants().len(), 4);
+        assert_eq!(Template::parse("A.A\n.A.\nA.A").unwrap().variants().len(), 1);
+        assert_eq!(Template::parse("XMAS").unwrap().variants().len(), 4);
+        assert_eq!(Template::parse("AB/C.").unwrap().variants().len(), 8);
+
+        let rotated = Template::parse("AB/C.").unwrap().rotated();
+        assert_eq!(rotated, Template::parse("CA/.B").unwrap());
+
+        assert!(Template::parse("AB/C").is_err());
+        assert!(Template::parse("").is_err());
+    }
+
+    #[test]
+    fn template_matches()
+    {
+        let grid = Grid::parse(EXAMPLE).unwrap();
+        assert_eq!(grid.count_template(&Template::parse("XMAS").unwrap()), 8);
+        // wildcard still has to be inside the grid
+        assert_eq!(Grid::parse("MaS\naAa\nMaS").unwrap().count_template(&Template::parse("M.S.").unwrap()), 0);
+    }
+
+    #[test]
+    fn match_locations()
+    {
+        let grid = Grid::parse("..X...\n.SAMX.\n.A..A.\nXMAS.S\n.X....").unwrap();
+        let found = grid.find_words(&["XMAS"]);
+
+        assert_eq!(found, vec![
+            Match { row: 0, col: 2, direction: (1, 1), word: "XMAS" },
+            Match { row: 1, col: 4, direction: (0, -1), word: "XMAS" },
+            Match { row: 3, col: 0, direction: (0, 1), word: "XMAS" },
+            Match { row: 4, col: 1, direction: (-1, 0), word: "XMAS" },
+        ]);
+        assert_eq!(direction_name(found[1].direction), "left");
+        assert_eq!(found[0].cells(&grid).collect::<Vec<_>>(), vec![(0, 2), (1, 3), (2, 4), (3, 5)]);
+    }
+
+    #[test]
+    fn render_matches()
+    {
+        let grid = Grid::parse("XMASZ\nZZAZZ\nZMZSZ").unwrap();
+        let highlighted: HashSet<(usize, usize)> = grid.find_words(&["XMAS"]).iter().flat_map(|x| x.cells(&grid)).collect();
+        assert_eq!(grid.render(&highlighted, false), "XMAS.\n.....\n.....\n");
+
+        let template = Template::parse("M.S/.A./M.S").unwrap();
+        let found = grid.find_template(&template);
+        assert_eq!(found.len(), 1);
+
+        let highlighted: HashSet<(usize, usize)> = found.iter().flat_map(|(row, col, variant)| variant.cells(&grid, *row, *col)).collect();
+        assert_eq!(grid.render(&highlighted, false), ".M.S.\n..A..\n.M.S.\n");
+        assert!(grid.render(&highlighted, true).contains(&format!("{COLOR_MATCH}A{COLOR_RESET}")));
+    }
+
+    fn grid_with(input: &str, options: SearchOptions) -> Grid
+    {
+        Grid { options, ..Grid::parse(input).unwrap() }
+    }
+
+    #[test]
+    fn wrap_around()
+    {
+        let wrapping = SearchOptions { wrap: true, ..SearchOptions::default() };
+        let horizontal = "ASXM\nZZZZ\nZZZZ\nZZZZ";
+        let vertical = "MZZZ\nAZZZ\nSZZZ\nXZZZ";
+        let diagonal = "ZZZS\nXZZZ\nZMZZ\nZZAZ";
+
+        for input in [horizontal, vertical, diagonal]
+        {
+            assert_eq!(Grid::parse(input).unwrap().count_word("XMAS"), 0);
+            assert_eq!(grid_with(input, wrapping.clone()).count_word("XMAS"), 1);
+        }
+
+        let grid = grid_with(horizontal, wrapping.clone());
+        let found = grid.find_words(&["XMAS"]);
+        assert_eq!(found[0].cells(&grid).collect::<Vec<_>>(), vec![(0, 2), (0, 3), (0, 0), (0, 1)]);
+
+        let template = Template::parse(X_MAS).unwrap();
+        assert_eq!(Grid::parse(".A.\nM.M\nS.S").unwrap().count_template(&template), 0);
+        assert_eq!(grid_with(".A.\nM.M\nS.S", wrapping).count_template(&template), 1);
+    }
+
+    #[test]
+    fn masked_cells()
+    {
+        let mask = parse_mask("....\n.#..\n#");
+        assert_eq!(mask, HashSet::from([(1, 1), (2, 0)]));
+
+        let grid = grid_with("XMAS\nXMAS\nXMAS", SearchOptions { mask, ..SearchOptions::default() });
+        assert_eq!(grid.count_word("XMAS"), 1);
+        assert_eq!(grid.count_word("X"), 2);
+    }
+
+    #[test]
+    fn restricted_directions()
+    {
+        let forward = SearchOptions { directions: parse_directions("right").unwrap(), ..SearchOptions::default() };
+        assert_eq!(grid_with(EXAMPLE, forward).count_word("XMAS"), 3);
+
+        // palindrome found once whether one or both readings are allowed
+        let backward = SearchOptions { directions: parse_directions("left").unwrap(), ..SearchOptions::default() };
+        assert_eq!(grid_with("ABA", backward).count_word("ABA"), 1);
+        let both = SearchOptions { directions: parse_directions("left, right").unwrap(), ..SearchOptions::default() };
+        assert_eq!(grid_with("ABA", both).count_word("ABA"), 1);
+
+        assert!(parse_directions("right,sideways").is_err());
+    }
+
+    #[test]
+    fn bitwise_matches_walk()
+    {
+        let words = ["XMAS", "SAM", "MAM", "AXA", "X", "XMASXMASX", ""];
+
+        for seed in 1..20
+        {
+            // wider than one u64, so shifts cross word boundaries
+            let input = generate_grid(150, 12, seed);
+            let grid = Grid::parse(&input).unwrap();
+            assert_eq!(grid.count_words_bitwise(&words), grid.count_words(&words), "{input}");
+
+            let options = SearchOptions { mask: parse_mask(&generate_grid(150, 12, seed + 100).replace('X', "#")),
+                directions: parse_directions("right,down-left,up,left").unwrap(), ..SearchOptions::default() };
+            let grid = grid_with(&input, options);
+            assert_eq!(grid.count_words_bitwise(&words), grid.count_words(&words));
+        }
+
+        assert_eq!(Grid::parse(EXAMPLE).unwrap().count_words_bitwise(&["XMAS"]), vec![18]);
+    }
+
+    #[test]
+    fn bits_at_offsets()
+    {
+        let row = [0b1011, 1 << 63 | 1];
+
+        assert_eq!(bits_at(&row, 0), 0b1011);
+        assert_eq!(bits_at(&row, 1), 0b101 | 1 << 63);
+        assert_eq!(bits_at(&row, 64), 1 << 63 | 1);
+        assert_eq!(bits_at(&row, 127), 1);
+        assert_eq!(bits_at(&row, -2), 0b101100);
+        assert_eq!(bits_at(&row, 128), 0);
+    }
+
+    #[test]
+    fn line_endings_and_ragged_rows()
+    {
+        let crlf = EXAMPLE.replace('\n', "\r\n");
+        assert_eq!(find_xmas(&crlf).unwrap(), 18);
+        assert_eq!(find_xmas_bonus(&crlf).unwrap(), 9);
+        assert_eq!(Grid::parse("AB\r\nCD\r\n\r\n").unwrap().rows.len(), 2);
+
+        let error = Grid::parse("ABC\nABC\nAB\nABC").unwrap_err();
+        assert_eq!(error.to_string(), "Row 3 has 2 cells, first row has 3");
+    }
+
+    #[test]
+    fn unicode_cells()
+    {
+        // decomposed é is one cell, different from e and from precomposed é
+        let grid = Grid::parse("ŽLUŤ\ne\u{301}ÉeL\nXŽ🎄U\nÚŤL🎄").unwrap();
+        assert_eq!(grid.rows[1].len(), 4);
+        assert_ne!(grid.rows[1][0], grid.rows[1][2]);
+
+        let words = ["ŽLUŤ", "ŤUL", "e\u{301}", "e", "é", "🎄L", "ŽÉ"];
+        assert_eq!(grid.count_words(&words), vec![1, 1, 1, 1, 0, 3, 2]);
+        assert_eq!(grid.count_words_bitwise(&words), grid.count_words(&words));
+
+        let found = grid.find_words(&["ŤUL"]);
+        assert_eq!(found, vec![Match { row: 0, col: 3, direction: (0, -1), word: "ŤUL" }]);
+        assert_eq!(found[0].cells(&grid).collect::<Vec<_>>(), vec![(0, 3), (0, 2), (0, 1)]);
+
+        let highlighted: HashSet<(usize, usize)> = found[0].cells(&grid).collect();
+        assert_eq!(grid.render(&highlighted, false), ".LUŤ\n....\n....\n....\n");
+
+        assert_eq!(grid.count_template(&Template::parse("Ž🎄").unwrap()), 1);
+        assert_eq!(parse_mask("ŽŽ#\n"), HashSet::from([(0, 2)]));
+    }
+}
@@ -1,5 +1,8 @@
 
-use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
+use std::collections::hash_map::Entry;
+use std::fmt;
 use std::io::{BufRead, BufReader};
 use std::fs::{exists, File};
 
@@ -129,13 +132,13 @@ fn transform_input(input: &Input) -> Data
         manuals: input.manuals.clone() }
 }
 
-fn count_valid_manuals(data: &Data) -> i32
+fn count_valid_manuals(data: &Data, rules: &RuleMatrix) -> i32
 {
     let mut sum = 0;
 
     data.manuals.iter().for_each(|manual|
         {
-            if is_manual_valid(manual, &data.follows)
+            if is_manual_valid(manual, rules)
             {
                 sum += extract_middle_page(manual);
             }
@@ -144,107 +147,556 @@ fn count_valid_manuals(data: &Data) -> i32
     return sum;
 }
 
-fn count_invalid_manuals(data: &Data) -> i32
+fn count_invalid_manuals(data: &Data, rules: &RuleMatrix) -> Result<i32, CycleError>
 {
     let mut sum = 0;
 
-    data.manuals.iter().for_each(|manual|
+    for manual in data.manuals.iter().filter(|manual| !is_manual_valid(manual, rules))
+    {
+        let reordered_manual = reorder_manual(manual, &data.follows)?;
+        sum += extract_middle_page(&reordered_manual);
+    }
+
+    Ok(sum)
+}
+
+#[derive(Debug, PartialEq)]
+struct Reordering
+{
+    pages: Vec<i32>,
+    // rules between pages of the manual allow more than one order, pages is one of them
+    ambiguous: bool,
+}
+
+#[derive(Debug, PartialEq)]
+struct CycleError
+{
+    // pages of the shortest cycle, first page is repeated at the end
+    cycle: Vec<i32>,
+    // manual whose pages form the cycle
+    manual: Vec<i32>,
+}
+
+impl fmt::Display for CycleError
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        write!(f, "Rules between pages of manual {:?} contain a cycle: {}", self.manual, format_cycle(&self.cycle))
+    }
+}
+
+impl std::error::Error for CycleError {}
+
+fn format_cycle(cycle: &[i32]) -> String
+{
+    cycle.iter().map(|x| x.to_string()).collect::<Vec<String>>().join(" -> ")
+}
+
+fn shortest_cycle(pages: &[i32], following: &HashMap<i32, Vec<i32>>) -> Option<Vec<i32>>
+// BFS from every page finds the shortest way back to it, rules with other pages are ignored
+{
+    let included: HashSet<i32> = pages.iter().copied().collect();
+    let mut shortest: Option<Vec<i32>> = None;
+
+    for &start in pages
+    {
+        // page each reached page was reached from
+        let mut parent: HashMap<i32, i32> = HashMap::new();
+        let mut queue: VecDeque<i32> = VecDeque::from([start]);
+
+        'search: while let Some(page) = queue.pop_front()
         {
-            if !is_manual_valid(manual, &data.follows)
+            for &next in following.get(&page).into_iter().flatten().filter(|x| included.contains(x))
             {
-                let reordered_manual = reorder_manual(manual, &data.follows);
-                sum += extract_middle_page(&reordered_manual);
+                if next == start
+                {
+                    let mut cycle = vec![page];
+                    while let Some(&previous) = parent.get(cycle.last().unwrap())
+                    {
+                        cycle.push(previous);
+                    }
+                    cycle.reverse();
+                    cycle.push(start);
+
+                    if shortest.as_ref().is_none_or(|x| cycle.len() < x.len())
+                    {
+                        shortest = Some(cycle);
+                    }
+                    break 'search;
+                }
+
+                if let Entry::Vacant(entry) = parent.entry(next)
+                {
+                    entry.insert(page);
+                    queue.push_back(next);
+                }
             }
-        });
+        }
+    }
 
-    return sum;
+    shortest
 }
 
-fn reorder_manual(manual: &Vec<i32>, following: &HashMap<i32, Vec<i32>>) -> Vec<i32>
+fn all_pages(following: &HashMap<i32, Vec<i32>>) -> Vec<i32>
+// every page mentioned by a rule, sorted
 {
-    /* 
-    find first character that breaks a rule
-    shift it to the left from the leftmost character whose rule is broken
-    (slowly swifting characters to the left but the rightmost correct position)
-    */
+    let mut pages: Vec<i32> = following.iter().flat_map(|(&x, ys)| std::iter::once(x).chain(ys.iter().copied())).collect();
+    pages.sort();
+    pages.dedup();
+    pages
+}
 
-    // TODO: maybe ineffective clone
-    let mut reordered = manual.clone();
+fn topological_order(manual: &[i32], following: &HashMap<i32, Vec<i32>>) -> Result<Reordering, CycleError>
+// Kahn's algorithm on rules between pages of the manual, when more pages are ready
+// to be printed, the one earlier in the manual goes first
+{
+    let position: HashMap<i32, usize> = manual.iter().enumerate().map(|(idx, &page)| (page, idx)).collect();
+    let followers = |page: i32| following.get(&page).into_iter().flatten().filter_map(|x| position.get(x).copied());
 
-    for _ in 0..manual.len() // boundary condition, worst case is moving each element once
+    // number of pages that have to be printed before the page, by position in manual
+    let mut blocking: Vec<usize> = vec![0; manual.len()];
+    for &page in manual
     {
-        match get_first_broken_rule_idx(&reordered, following)
+        followers(page).for_each(|idx| blocking[idx] += 1);
+    }
+
+    let mut ready: BinaryHeap<Reverse<usize>> = (0..manual.len()).filter(|&idx| blocking[idx] == 0).map(Reverse).collect();
+    let mut pages: Vec<i32> = Vec::with_capacity(manual.len());
+    let mut ambiguous = false;
+
+    while let Some(Reverse(idx)) = ready.pop()
+    {
+        // other ready page could have been printed instead
+        ambiguous |= !ready.is_empty();
+        pages.push(manual[idx]);
+
+        for follower in followers(manual[idx])
         {
-            None => { return reordered; }
-            Some(idx) => 
+            blocking[follower] -= 1;
+
+            if blocking[follower] == 0
             {
-                // guaranteed that idx is safe by called function
-                // CLONE must be here -> we can't hold borrowed internals of a object 
-                //      (e.g. iterator) and change the object (because after changing 
-                //      the object, the reference might become invalid)
-                let page = reordered.get(idx).unwrap().clone();
-
-                // since page has broken some rules, its guaranteed that it has some rules => safe
-                let rules = following.get(&page).unwrap();
-
-                // we know that some of the elements must be in the rules => safe
-                let first_follower_idx = reordered.iter()
-                    .position(|i| rules.contains(i))
-                    .unwrap();
-
-                // delete old position of faulty page
-                reordered.remove(idx);
-                // insert it to the new position
-                reordered.insert(first_follower_idx, page);
+                ready.push(Reverse(follower));
             }
         }
     }
 
-    panic!("Probably iterating for too long over manual: {manual:?}");
+    if pages.len() != manual.len()
+    {
+        // pages on a cycle never become ready
+        let cycle = shortest_cycle(manual, following).unwrap_or_default();
+        return Err(CycleError { cycle, manual: manual.to_vec() });
+    }
+
+    Ok(Reordering { pages, ambiguous })
+}
+
+fn reorder_manual(manual: &[i32], following: &HashMap<i32, Vec<i32>>) -> Result<Vec<i32>, CycleError>
+{
+    Ok(topological_order(manual, following)?.pages)
+}
+
+#[derive(Debug, PartialEq)]
+struct BrokenRule
+// rule before|after with after printed first, positions are 0-based
+{
+    before: i32,
+    after: i32,
+    before_idx: usize,
+    after_idx: usize,
+}
+
+impl fmt::Display for BrokenRule
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        write!(f, "rule {}|{}: {} at position {}, {} at position {}", self.before, self.after,
+            self.before, self.before_idx + 1, self.after, self.after_idx + 1)
+    }
 }
 
-fn get_first_broken_rule_idx(manual: &Vec<i32>, following: &HashMap<i32, Vec<i32>>) -> Option<usize>
+fn broken_rules(manual: &[i32], following: &HashMap<i32, Vec<i32>>) -> Vec<BrokenRule>
+// every rule whose second page is printed before the first one, by position of the first page
 {
-    // tracks encountered pages for each iteration (iterating over pages in manual)
-    let mut encountered: HashSet<&i32> = HashSet::new();
+    let position: HashMap<i32, usize> = manual.iter().enumerate().map(|(idx, &page)| (page, idx)).collect();
+    let mut broken: Vec<BrokenRule> = Vec::new();
 
-    for idx in 0..manual.len()
+    for (before_idx, &before) in manual.iter().enumerate()
     {
-        let page = manual.get(idx).unwrap();
+        let mut rules: Vec<BrokenRule> = following.get(&before).into_iter().flatten()
+            .filter_map(|&after| position.get(&after).filter(|&&after_idx| after_idx < before_idx)
+                .map(|&after_idx| BrokenRule { before, after, before_idx, after_idx }))
+            .collect();
+
+        rules.sort_by_key(|x| x.after_idx);
+        broken.extend(rules);
+    }
+
+    broken
+}
 
-        match following.get(page)
+fn print_report(data: &Data)
+// broken rules and fixed order of every invalid manual
+{
+    for (idx, manual) in data.manuals.iter().enumerate()
+    {
+        let broken = broken_rules(manual, &data.follows);
+
+        if broken.is_empty()
         {
-            // no rules (following pages) for this page
-            None => (),
-            // make sure that no following pages (from the rules) were encountered
-            Some(following_pages) => 
+            continue;
+        }
+
+        println!("Manual {}: {}", idx + 1, format_manual(manual));
+
+        for rule in &broken
+        {
+            println!("  {rule}");
+        }
+
+        match topological_order(manual, &data.follows)
+        {
+            Ok(fixed) if fixed.ambiguous => println!("  fixed order: {} (one of more)", format_manual(&fixed.pages)),
+            Ok(fixed) => println!("  fixed order: {}", format_manual(&fixed.pages)),
+            Err(e) => println!("  {e}"),
+        }
+
+        if let Ok(fix) = fewest_moves(manual, &data.follows)
+        {
+            println!("  fewest moves: {}, giving {}", fix.moves.len(), format_manual(&fix.pages));
+
+            for step in &fix.moves
             {
-                // compute intersection between following pages (according to rules) and encountered pages
-                let following_pages_set = HashSet::from_iter(following_pages.iter());
-                let intersect: Vec<_> = encountered.intersection(&following_pages_set).collect();
+                println!("    {step}");
+            }
+        }
+    }
+}
+
+fn format_manual(manual: &[i32]) -> String
+{
+    manual.iter().map(|x| x.to_string()).collect::<Vec<String>>().join(",")
+}
 
-                // intersection should be empty (no following page should have been encountered before)
-                if intersect.len() != 0
+#[derive(Debug, PartialEq)]
+struct Move
+// page is taken out and put right after another page, or in front of all pages
+{
+    page: i32,
+    after: Option<i32>,
+}
+
+impl fmt::Display for Move
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match self.after
+        {
+            Some(after) => write!(f, "move page {} after {after}", self.page),
+            None => write!(f, "move page {} to the front", self.page),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Fix
+{
+    // valid order reachable with fewest moves
+    pages: Vec<i32>,
+    // to be done one by one, in this order
+    moves: Vec<Move>,
+}
+
+fn fewest_moves(manual: &[i32], following: &HashMap<i32, Vec<i32>>) -> Result<Fix, CycleError>
+// pages that are not moved keep their order, so they must not contain two pages where the later
+// one has to be printed before the earlier one, fewest moves keep the largest such set
+{
+    topological_order(manual, following)?;
+
+    let size = manual.len();
+    let position: HashMap<i32, usize> = manual.iter().enumerate().map(|(idx, &page)| (page, idx)).collect();
+    let followers = |page: i32| following.get(&page).into_iter().flatten().filter_map(|x| position.get(x).copied());
+
+    // precedes[i][j]: page at i has to be printed before page at j, directly or through other pages of the manual
+    let mut precedes: Vec<Vec<bool>> = vec![vec![false; size]; size];
+    for (idx, &page) in manual.iter().enumerate()
+    {
+        followers(page).for_each(|follower| precedes[idx][follower] = true);
+    }
+    for k in 0..size
+    {
+        for i in 0..size
+        {
+            for j in 0..size
+            {
+                precedes[i][j] |= precedes[i][k] && precedes[k][j];
+            }
+        }
+    }
+
+    // inverted[i][j]: i < j, but page at j has to be printed first, this is a partial order and
+    // kept pages are its largest antichain, by Dilworth's theorem it has size - max matching pages
+    let inverted: Vec<Vec<bool>> = (0..size).map(|i| (0..size).map(|j| i < j && precedes[j][i]).collect()).collect();
+
+    // matched[j]: i of inverted pair (i, j) in the matching
+    let mut matched: Vec<Option<usize>> = vec![None; size];
+    for i in 0..size
+    {
+        augment(i, &inverted, &mut vec![false; size], &mut matched);
+    }
+
+    // König's theorem: pages reachable by alternating paths from unmatched left side,
+    // kept pages are reached on the left side and not on the right side
+    let mut matched_left: Vec<bool> = vec![false; size];
+    matched.iter().flatten().for_each(|&i| matched_left[i] = true);
+
+    let (mut left, mut right) = (vec![false; size], vec![false; size]);
+    let mut stack: Vec<usize> = (0..size).filter(|&i| !matched_left[i]).collect();
+    stack.iter().for_each(|&i| left[i] = true);
+
+    while let Some(i) = stack.pop()
+    {
+        for j in (0..size).filter(|&j| inverted[i][j] && matched[j] != Some(i))
+        {
+            if !right[j]
+            {
+                right[j] = true;
+
+                if let Some(k) = matched[j].filter(|&k| !left[k])
                 {
-                    return Some(idx);
+                    left[k] = true;
+                    stack.push(k);
                 }
             }
         }
+    }
+
+    let kept: Vec<i32> = (0..size).filter(|&idx| left[idx] && !right[idx]).map(|idx| manual[idx]).collect();
+
+    // rules between pages of the manual, kept pages are chained in their current order
+    let mut rules: HashMap<i32, Vec<i32>> = manual.iter().map(|&page| (page, followers(page).map(|idx| manual[idx]).collect())).collect();
+    for pair in kept.windows(2)
+    {
+        rules.entry(pair[0]).or_default().push(pair[1]);
+    }
+
+    let pages = topological_order(manual, &rules)?.pages;
+
+    // every moved page goes right after the page before it in the new order, which was either
+    // kept or already moved, so moving them in the new order builds it up
+    let moves: Vec<Move> = pages.iter().enumerate()
+        .filter(|(_, page)| !kept.contains(page))
+        .map(|(idx, &page)| Move { page, after: idx.checked_sub(1).map(|x| pages[x]) })
+        .collect();
+
+    Ok(Fix { pages, moves })
+}
+
+fn augment(i: usize, inverted: &[Vec<bool>], visited: &mut [bool], matched: &mut [Option<usize>]) -> bool
+// Kuhn's augmenting path from left page i
+{
+    for j in 0..inverted.len()
+    {
+        if inverted[i][j] && !visited[j]
+        {
+            visited[j] = true;
+
+            let free = match matched[j]
+            {
+                Some(k) => augment(k, inverted, visited, matched),
+                None => true,
+            };
 
-        encountered.insert(page);
+            if free
+            {
+                matched[j] = Some(i);
+                return true;
+            }
+        }
     }
 
-    // no rule broken
-    return None
+    false
 }
 
-fn is_manual_valid(manual: &Vec<i32>, following: &HashMap<i32, Vec<i32>>) -> bool
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum GraphFormat
 {
-    match get_first_broken_rule_idx(manual, following)
+    Dot,
+    Mermaid,
+}
+
+fn export_graph(following: &HashMap<i32, Vec<i32>>, manual: Option<&[i32]>, format: GraphFormat) -> String
+// rules as edges, either all of them or only those between pages of the manual,
+// broken rules of the manual are drawn red
+{
+    let pages: Vec<i32> = match manual
+    {
+        Some(manual) => manual.to_vec(),
+        None => all_pages(following),
+    };
+    let included: HashSet<i32> = pages.iter().copied().collect();
+    let broken: HashSet<(i32, i32)> = manual.map_or(HashSet::new(),
+        |manual| broken_rules(manual, following).iter().map(|x| (x.before, x.after)).collect());
+
+    let mut edges: Vec<(i32, i32)> = following.iter()
+        .filter(|(x, _)| included.contains(x))
+        .flat_map(|(&x, ys)| ys.iter().filter(|y| included.contains(y)).map(move |&y| (x, y)))
+        .collect();
+    edges.sort();
+    edges.dedup();
+
+    let mut lines: Vec<String> = Vec::new();
+
+    match format
     {
-        None => true,
-        Some(_) => false,
+        GraphFormat::Dot =>
+        {
+            lines.push("digraph rules {".to_string());
+            lines.push("    rankdir=LR;".to_string());
+            lines.extend(pages.iter().map(|page| format!("    {page};")));
+
+            for (x, y) in edges
+            {
+                match broken.contains(&(x, y))
+                {
+                    true => lines.push(format!("    {x} -> {y} [color=red, penwidth=2];")),
+                    false => lines.push(format!("    {x} -> {y};")),
+                }
+            }
+            lines.push("}".to_string());
+        },
+        GraphFormat::Mermaid =>
+        {
+            lines.push("flowchart LR".to_string());
+            lines.extend(pages.iter().map(|page| format!("    p{page}[{page}]")));
+            lines.extend(edges.iter().map(|(x, y)| format!("    p{x} --> p{y}")));
+
+            // links are styled by their index
+            lines.extend(edges.iter().enumerate()
+                .filter(|(_, edge)| broken.contains(edge))
+                .map(|(idx, _)| format!("    linkStyle {idx} stroke:red,stroke-width:2px")));
+        },
     }
+
+    lines.join("\n") + "\n"
+}
+
+// largest page ID with a row in the bit-matrix
+const MAX_PAGE: i32 = 4095;
+
+struct RuleMatrix
+// rules indexed for validation, followers of every page and, when all page IDs
+// are small, a bit-matrix where bit y of row x is set for rule x|y
+{
+    followers: HashMap<i32, Vec<i32>>,
+    // None when a page in rules is out of 0..=MAX_PAGE, then only followers are used
+    bits: Option<Vec<u64>>,
+    // pages 0..size have a row of stride u64
+    size: usize,
+    stride: usize,
+}
+
+impl RuleMatrix
+{
+    fn new(following: &HashMap<i32, Vec<i32>>) -> RuleMatrix
+    {
+        let mut followers = following.clone();
+        followers.values_mut().for_each(|ys| { ys.sort(); ys.dedup(); });
+
+        let pages = all_pages(following);
+
+        if pages.iter().any(|x| !(0..=MAX_PAGE).contains(x))
+        {
+            return RuleMatrix { followers, bits: None, size: 0, stride: 0 };
+        }
+
+        let size = pages.last().map_or(0, |&x| x as usize + 1);
+        let stride = size.div_ceil(64);
+        let mut bits = vec![0u64; size * stride];
+
+        for (&x, ys) in following
+        {
+            for &y in ys
+            {
+                bits[x as usize * stride + y as usize / 64] |= 1 << (y % 64);
+            }
+        }
+
+        RuleMatrix { followers, bits: Some(bits), size, stride }
+    }
+
+    fn has_rule(&self, bits: &[u64], before: i32, after: i32) -> bool
+    // before has to have a row, after may be any page
+    {
+        (0..self.size as i32).contains(&after)
+            && bits[before as usize * self.stride + after as usize / 64] >> (after % 64) & 1 == 1
+    }
+
+    fn walk_broken(&self, manual: &[i32], mut broken: impl FnMut(usize, usize) -> bool)
+    // calls broken with positions of the first and the second page of each broken rule,
+    // by position of the first page, stops when it returns false, every page checks
+    // either its followers or the pages printed before it, whichever is fewer, so
+    // n pages cost O(n) for the position map plus O(min(followers, position)) per page,
+    // followers missing from the manual are paid for even though they break no rule
+    {
+        let position: HashMap<i32, usize> = manual.iter().enumerate().map(|(idx, &page)| (page, idx)).collect();
+
+        for (before_idx, &before) in manual.iter().enumerate()
+        {
+            let followers = self.followers.get(&before).map_or(&[][..], |x| x.as_slice());
+
+            match &self.bits
+            {
+                Some(bits) if before_idx < followers.len() =>
+                {
+                    for (after_idx, &after) in manual[..before_idx].iter().enumerate()
+                    {
+                        if self.has_rule(bits, before, after) && !broken(before_idx, after_idx)
+                        {
+                            return;
+                        }
+                    }
+                },
+                _ =>
+                {
+                    for after_idx in followers.iter().filter_map(|x| position.get(x).copied())
+                    {
+                        if after_idx < before_idx && !broken(before_idx, after_idx)
+                        {
+                            return;
+                        }
+                    }
+                },
+            }
+        }
+    }
+}
+
+fn count_manuals_parallel(data: &Data, rules: &RuleMatrix) -> Result<(i32, i32), CycleError>
+// sums of middle pages of valid and of reordered invalid manuals, manuals are checked in parallel
+{
+    use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+
+    data.manuals.par_iter()
+        .map(|manual| match is_manual_valid(manual, rules)
+        {
+            true => Ok((extract_middle_page(manual), 0)),
+            false => Ok((0, extract_middle_page(&reorder_manual(manual, &data.follows)?))),
+        })
+        .try_reduce(|| (0, 0), |a, b| Ok((a.0 + b.0, a.1 + b.1)))
+}
+
+fn get_first_broken_rule_idx(manual: &[i32], rules: &RuleMatrix) -> Option<usize>
+// position of the first page with a rule broken by a page printed before it
+{
+    let mut first: Option<usize> = None;
+    rules.walk_broken(manual, |before_idx, _| { first = Some(before_idx); false });
+    first
+}
+
+fn is_manual_valid(manual: &[i32], rules: &RuleMatrix) -> bool
+{
+    get_first_broken_rule_idx(manual, rules).is_none()
 }
 
 fn extract_middle_page(manual: &Vec<i32>) -> i32
@@ -259,18 +711,82 @@ fn extract_middle_page(manual: &Vec<i32>) -> i32
 
 fn main() -> Result<(), Box<dyn std::error::Error>>
 {
-    let input = get_input(r"D:\src\Advent2024\inputs\05.txt")?;
+    let mut file_path = r"D:\src\Advent2024\inputs\05.txt".to_string();
+    let (mut report, mut parallel) = (false, false);
+    let mut graph: Option<GraphFormat> = None;
+    let mut graph_manual: Option<usize> = None;
+
+    let mut args = std::env::args().skip(1);
+
+    while let Some(arg) = args.next()
+    {
+        match arg.as_str()
+        {
+            "--report" => report = true,
+            "--parallel" => parallel = true,
+            "--graph" => graph = match args.next().as_deref()
+            {
+                Some("dot") => Some(GraphFormat::Dot),
+                Some("mermaid") => Some(GraphFormat::Mermaid),
+                _ => return Err("Expected dot or mermaid after --graph".into()),
+            },
+            // 1-based, graph of rules between pages of this manual only
+            "--manual" => graph_manual = Some(args.next().ok_or("Missing manual number for --manual")?.parse()?),
+            _ if arg.starts_with("--") => return Err(format!("Unknown option {arg}").into()),
+            _ => file_path = arg,
+        }
+    }
+
+    if graph_manual.is_some() && graph.is_none()
+    {
+        return Err("--manual selects the manual for --graph, use them together".into());
+    }
+
+    let input = get_input(&file_path)?;
     // println!("{input:?}");
 
     let data = transform_input(&input);
     // println!("{data:?}");
 
-    let valid_cnt = count_valid_manuals(&data);
-    println!("Result: {valid_cnt}");
+    // only pages of one manual have to be ordered, so this alone is not an error
+    if let Some(cycle) = shortest_cycle(&all_pages(&data.follows), &data.follows)
+    {
+        println!("Warning: rules contain a cycle: {}", format_cycle(&cycle));
+    }
+
+    let rules = RuleMatrix::new(&data.follows);
 
-    let bonus_cnt = count_invalid_manuals(&data);
+    let (valid_cnt, bonus_cnt) = match parallel
+    {
+        true => count_manuals_parallel(&data, &rules)?,
+        false => (count_valid_manuals(&data, &rules), count_invalid_manuals(&data, &rules)?),
+    };
+    println!("Result: {valid_cnt}");
     println!("Bonus: {bonus_cnt}");
 
+    for (idx, manual) in data.manuals.iter().enumerate().filter(|(_, manual)| !is_manual_valid(manual, &rules))
+    {
+        if topological_order(manual, &data.follows)?.ambiguous
+        {
+            println!("Warning: rules allow more orders of manual {} {manual:?}, middle page may differ", idx + 1);
+        }
+    }
+
+    if report
+    {
+        print_report(&data);
+    }
+
+    if let Some(format) = graph
+    {
+        let manual = match graph_manual
+        {
+            Some(idx) => Some(idx.checked_sub(1).and_then(|x| data.manuals.get(x)).ok_or(format!("Manual {idx} does not exist"))?.as_slice()),
+            None => None,
+        };
+        print!("{}", export_graph(&data.follows, manual, format));
+    }
+
     // for each page, store pages that can't follow it 
     // everytime when page is added, check that it was not encountered before
     return Ok(());
@@ -286,7 +802,7 @@ mod tests
     #[test]
     fn valid_no_prohibited() 
     {
-        let result = is_manual_valid(&vec![1, 2, 3], &HashMap::new());
+        let result = is_manual_valid(&[1, 2, 3], &RuleMatrix::new(&HashMap::new()));
         assert_eq!(result, true);
     }
 
@@ -298,7 +814,7 @@ mod tests
             (1, vec![2, 3]),
             (2, vec![3]),
             ]);
-        let result = is_manual_valid(&vec![1, 2, 3], &preceding);
+        let result = is_manual_valid(&[1, 2, 3], &RuleMatrix::new(&preceding));
         assert_eq!(result, true);
     }
 
@@ -309,7 +825,7 @@ mod tests
         let preceding = HashMap::from([
             (2, vec![1]),
             ]);
-        let result = is_manual_valid(&vec![1, 2, 3], &preceding);
+        let result = is_manual_valid(&[1, 2, 3], &RuleMatrix::new(&preceding));
         assert_eq!(result, false);
     }
 
@@ -319,7 +835,7 @@ mod tests
         let preceding = HashMap::from([
             (4, vec![2, 3]),
             ]);
-        let result = reorder_manual(&vec![1, 2, 3, 4, 5], &preceding);
+        let result = reorder_manual(&[1, 2, 3, 4, 5], &preceding).unwrap();
         assert_eq!(result, vec![1,4,2,3,5]);
     }
 
@@ -330,7 +846,7 @@ mod tests
             (4, vec![2, 3]),
             (5, vec![3]),
             ]);
-        let result = reorder_manual(&vec![1, 2, 3, 4, 5], &preceding);
+        let result = reorder_manual(&[1, 2, 3, 4, 5], &preceding).unwrap();
         assert_eq!(result, vec![1,4,2,5,3]);
     }
 
@@ -343,7 +859,7 @@ mod tests
             (4, vec![3]),
             (5, vec![4]),
             ]);
-        let result = reorder_manual(&vec![1, 2, 3, 4, 5], &preceding);
+        let result = reorder_manual(&[1, 2, 3, 4, 5], &preceding).unwrap();
         assert_eq!(result, vec![5,4,3,2,1]);
     }
 
@@ -354,8 +870,8 @@ mod tests
             (3, vec![2]),
             (5, vec![1]),
             ]);
-        let result = reorder_manual(&vec![1, 2, 3, 4, 5], &preceding);
-        assert_eq!(result, vec![3,2,5,1,4]);
+        let result = reorder_manual(&[1, 2, 3, 4, 5], &preceding).unwrap();
+        assert_eq!(result, vec![3,2,4,5,1]);
     }
 
     #[test]
@@ -365,15 +881,72 @@ mod tests
             (3, vec![1]),
             (5, vec![2]),
             ]);
-        let result = reorder_manual(&vec![1, 2, 3, 4, 5], &preceding);
-        assert_eq!(result, vec![3,1,5,2,4]);
+        let result = reorder_manual(&[1, 2, 3, 4, 5], &preceding).unwrap();
+        assert_eq!(result, vec![3,1,4,5,2]);
     }
 
+    #[test]
+    fn ambiguous_order()
+    {
+        // chain has exactly one order
+        let chain = HashMap::from([
+            (3, vec![2]),
+            (2, vec![1]),
+            ]);
+        assert_eq!(topological_order(&[1, 2, 3], &chain), Ok(Reordering { pages: vec![3, 2, 1], ambiguous: false }));
+
+        // 4 and 5 can go anywhere
+        let preceding = HashMap::from([
+            (3, vec![2]),
+            (5, vec![1]),
+            ]);
+        assert!(topological_order(&[1, 2, 3, 4, 5], &preceding).unwrap().ambiguous);
+
+        // order is ambiguous, ready pages are taken by position in the manual, so 4 keeps its place
+        assert_eq!(topological_order(&[4, 1, 2, 3], &chain).unwrap().pages, vec![4, 3, 2, 1]);
+        assert_eq!(topological_order(&[3, 4, 2, 1], &chain).unwrap().pages, vec![3, 4, 2, 1]);
+    }
+
+    #[test]
+    fn shortest_cycles()
+    {
+        let rules = HashMap::from([
+            (1, vec![2]),
+            (2, vec![3, 5]),
+            (3, vec![4]),
+            (4, vec![1]),
+            (5, vec![1]),
+            (6, vec![6]),
+            ]);
+
+        assert_eq!(shortest_cycle(&[1, 2, 3, 4, 5], &rules), Some(vec![1, 2, 5, 1]));
+        assert_eq!(shortest_cycle(&[1, 2, 3, 4], &rules), Some(vec![1, 2, 3, 4, 1]));
+        assert_eq!(shortest_cycle(&[2, 3, 4, 5], &rules), None);
+        assert_eq!(shortest_cycle(&all_pages(&rules), &rules), Some(vec![6, 6]));
+        assert_eq!(all_pages(&rules), vec![1, 2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn cycle_in_manual()
+    {
+        let rules = HashMap::from([
+            (1, vec![2]),
+            (2, vec![3]),
+            (3, vec![1]),
+            ]);
+
+        // cycle is not closed without page 3
+        assert_eq!(reorder_manual(&[2, 1, 4], &rules), Ok(vec![1, 2, 4]));
+
+        let error = reorder_manual(&[4, 3, 2, 1], &rules).unwrap_err();
+        assert_eq!(error.cycle, vec![3, 1, 2, 3]);
+        assert_eq!(error.to_string(), "Rules between pages of manual [4, 3, 2, 1] contain a cycle: 3 -> 1 -> 2 -> 3");
+    }
 
     #[test]
     fn first_broken_valid() 
     {
-        let result = get_first_broken_rule_idx(&vec![1, 2, 3], &HashMap::new());
+        let result = get_first_broken_rule_idx(&[1, 2, 3], &RuleMatrix::new(&HashMap::new()));
         assert_eq!(result, None);
     }
 
@@ -383,7 +956,242 @@ mod tests
         let preceding = HashMap::from([
             (4, vec![2, 3]),
             ]);
-        let result = get_first_broken_rule_idx(&vec![1, 2, 3, 4, 5], &preceding);
+        let result = get_first_broken_rule_idx(&[1, 2, 3, 4, 5], &RuleMatrix::new(&preceding));
         assert_eq!(result, Some(3));
     }
-}
\ No newline at end of file
+
+    const EXAMPLE_RULES: &str = "47|53\n97|13\n97|61\n97|47\n75|29\n61|13\n75|53\n29|13\n97|29\n53|29\n61|53\n\
+        97|53\n61|29\n47|13\n75|47\n97|75\n47|61\n75|61\n47|29\n75|13\n53|13";
+    const EXAMPLE_MANUALS: &str = "75,47,61,53,29\n97,61,53,29,13\n75,29,13\n75,97,47,61,53\n61,13,29\n97,13,75,29,47";
+
+    fn example_data() -> Data
+    {
+        let input = Input {
+            rules: EXAMPLE_RULES.lines().map(|x| parse_rule_line(&x.to_string()).unwrap()).collect(),
+            manuals: EXAMPLE_MANUALS.lines().map(|x| parse_manual_line(&x.to_string()).unwrap()).collect(),
+        };
+        transform_input(&input)
+    }
+
+    #[test]
+    fn example()
+    {
+        let data = example_data();
+        let rules = RuleMatrix::new(&data.follows);
+        assert_eq!(count_valid_manuals(&data, &rules), 143);
+        assert_eq!(count_invalid_manuals(&data, &rules), Ok(123));
+    }
+
+    #[test]
+    fn broken_rule_positions()
+    {
+        let data = example_data();
+
+        assert_eq!(broken_rules(&data.manuals[0], &data.follows), vec![]);
+        assert_eq!(broken_rules(&data.manuals[3], &data.follows),
+            vec![BrokenRule { before: 97, after: 75, before_idx: 1, after_idx: 0 }]);
+
+        let broken: Vec<String> = broken_rules(&data.manuals[5], &data.follows).iter().map(|x| x.to_string()).collect();
+        assert_eq!(broken, vec![
+            "rule 75|13: 75 at position 3, 13 at position 2",
+            "rule 29|13: 29 at position 4, 13 at position 2",
+            "rule 47|13: 47 at position 5, 13 at position 2",
+            "rule 47|29: 47 at position 5, 29 at position 4",
+        ]);
+        assert_eq!(reorder_manual(&data.manuals[5], &data.follows), Ok(vec![97, 75, 47, 29, 13]));
+    }
+
+    #[test]
+    fn graph_export()
+    {
+        let data = example_data();
+        let manual = [61, 13, 29];
+
+        assert_eq!(export_graph(&data.follows, Some(&manual), GraphFormat::Dot),
+            "digraph rules {\n    rankdir=LR;\n    61;\n    13;\n    29;\n    \
+            29 -> 13 [color=red, penwidth=2];\n    61 -> 13;\n    61 -> 29;\n}\n");
+
+        assert_eq!(export_graph(&data.follows, Some(&manual), GraphFormat::Mermaid),
+            "flowchart LR\n    p61[61]\n    p13[13]\n    p29[29]\n    p29 --> p13\n    p61 --> p13\n    p61 --> p29\n    \
+            linkStyle 0 stroke:red,stroke-width:2px\n");
+
+        // whole graph, nothing is broken
+        let dot = export_graph(&data.follows, None, GraphFormat::Dot);
+        assert_eq!(dot.matches(" -> ").count(), 21);
+        assert!(!dot.contains("color=red"));
+    }
+
+    // HashSet of printed pages intersected with followers of every page
+    fn naive_first_broken_rule_idx(manual: &[i32], following: &HashMap<i32, Vec<i32>>) -> Option<usize>
+    {
+        // tracks encountered pages for each iteration (iterating over pages in manual)
+        let mut encountered: HashSet<&i32> = HashSet::new();
+
+        for idx in 0..manual.len()
+        {
+            let page = manual.get(idx).unwrap();
+
+            match following.get(page)
+            {
+                // no rules (following pages) for this page
+                None => (),
+                // make sure that no following pages (from the rules) were encountered
+                Some(following_pages) => 
+                {
+                    // compute intersection between following pages (according to rules) and encountered pages
+                    let following_pages_set = HashSet::from_iter(following_pages.iter());
+                    let intersect: Vec<_> = encountered.intersection(&following_pages_set).collect();
+
+                    // intersection should be empty (no following page should have been encountered before)
+                    if !intersect.is_empty()
+                    {
+                        return Some(idx);
+                    }
+                }
+            }
+
+            encountered.insert(page);
+        }
+
+        // no rule broken
+        None
+    }
+
+    fn shuffled_manual(state: &mut u64, pages: &[i32], min_len: usize) -> Vec<i32>
+    // pages shuffled by xorshift and cut to min_len..=pages.len() pages
+    {
+        let mut next = ||
+        {
+            *state ^= *state << 13;
+            *state ^= *state >> 7;
+            *state ^= *state << 17;
+            *state
+        };
+
+        let mut manual = pages.to_vec();
+        for idx in (1..manual.len()).rev()
+        {
+            manual.swap(idx, (next() % (idx as u64 + 1)) as usize);
+        }
+        manual.truncate(min_len + (*state % (pages.len() - min_len + 1) as u64) as usize);
+        manual
+    }
+
+    #[test]
+    fn rule_matrix_matches_rules()
+    {
+        let data = example_data();
+        // same rules with page IDs too large for the bit-matrix
+        let shifted: HashMap<i32, Vec<i32>> = data.follows.iter()
+            .map(|(&x, ys)| (x + 10_000, ys.iter().map(|y| y + 10_000).collect()))
+            .collect();
+        let (rules, shifted_rules) = (RuleMatrix::new(&data.follows), RuleMatrix::new(&shifted));
+        assert!(rules.bits.is_some() && shifted_rules.bits.is_none());
+
+        let mut state: u64 = 7;
+
+        for _ in 0..500
+        {
+            let manual = shuffled_manual(&mut state, &[97, 75, 47, 61, 53, 29, 13, 5, 150], 3);
+
+            let expected_first = naive_first_broken_rule_idx(&manual, &data.follows);
+            assert_eq!(get_first_broken_rule_idx(&manual, &rules), expected_first, "{manual:?}");
+
+            let mut expected: Vec<(usize, usize)> = broken_rules(&manual, &data.follows).iter()
+                .map(|x| (x.before_idx, x.after_idx)).collect();
+            expected.sort();
+
+            let shifted_manual: Vec<i32> = manual.iter().map(|x| x + 10_000).collect();
+            assert_eq!(get_first_broken_rule_idx(&shifted_manual, &shifted_rules), expected_first, "{manual:?}");
+
+            for (rules, manual) in [(&rules, &manual), (&shifted_rules, &shifted_manual)]
+            {
+                let mut broken: Vec<(usize, usize)> = Vec::new();
+                rules.walk_broken(manual, |before, after| { broken.push((before, after)); true });
+                broken.sort();
+                assert_eq!(broken, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn parallel_example()
+    {
+        let data = example_data();
+        assert_eq!(count_manuals_parallel(&data, &RuleMatrix::new(&data.follows)), Ok((143, 123)));
+
+        // out of range of the bit-matrix, both ways still agree
+        let data = Data { follows: HashMap::from([(5000, vec![1]), (1, vec![-1])]), manuals: vec![vec![-1, 1, 5000], vec![5000, 1, -1]] };
+        let rules = RuleMatrix::new(&data.follows);
+        assert_eq!(count_manuals_parallel(&data, &rules), Ok((1, 1)));
+        assert_eq!((count_valid_manuals(&data, &rules), count_invalid_manuals(&data, &rules)), (1, Ok(1)));
+    }
+
+    fn apply_moves(manual: &[i32], moves: &[Move]) -> Vec<i32>
+    {
+        let mut pages = manual.to_vec();
+
+        for step in moves
+        {
+            pages.retain(|&x| x != step.page);
+            let idx = step.after.map_or(0, |after| pages.iter().position(|&x| x == after).unwrap() + 1);
+            pages.insert(idx, step.page);
+        }
+
+        pages
+    }
+
+    #[test]
+    fn fewest_moves_example()
+    {
+        let data = example_data();
+        let counts: Vec<usize> = data.manuals.iter().map(|x| fewest_moves(x, &data.follows).unwrap().moves.len()).collect();
+        assert_eq!(counts, [0, 0, 0, 1, 1, 2]);
+
+        let fix = fewest_moves(&data.manuals[3], &data.follows).unwrap();
+        // moving 97 to the front is as good
+        assert_eq!(fix.moves, [Move { page: 75, after: Some(97) }]);
+        assert_eq!(Move { page: 97, after: None }.to_string(), "move page 97 to the front");
+
+        let fix = fewest_moves(&data.manuals[4], &data.follows).unwrap();
+        assert_eq!(fix.pages, [61, 29, 13]);
+        assert_eq!(fix.moves[0].to_string(), "move page 13 after 29");
+    }
+
+    #[test]
+    fn fewest_moves_brute_force()
+    {
+        let data = example_data();
+        let mut state: u64 = 11;
+
+        for _ in 0..100
+        {
+            let manual = shuffled_manual(&mut state, &[97, 75, 47, 61, 53, 29, 13, 150], 2);
+
+            let fix = fewest_moves(&manual, &data.follows).unwrap();
+            assert_eq!(apply_moves(&manual, &fix.moves), fix.pages);
+            assert!(is_manual_valid(&fix.pages, &RuleMatrix::new(&data.follows)), "{manual:?}");
+
+            // largest subset of pages that can keep their order in some valid order
+            let largest = (0u32..1 << manual.len())
+                .filter(|mask|
+                {
+                    let kept: Vec<i32> = manual.iter().enumerate().filter(|(idx, _)| mask & (1 << idx) != 0).map(|(_, &x)| x).collect();
+                    let mut rules = data.follows.clone();
+                    kept.windows(2).for_each(|pair| rules.entry(pair[0]).or_default().push(pair[1]));
+                    topological_order(&manual, &rules).is_ok()
+                })
+                .map(|mask| mask.count_ones() as usize)
+                .max()
+                .unwrap();
+            assert_eq!(fix.moves.len(), manual.len() - largest, "{manual:?}");
+        }
+    }
+
+    #[test]
+    fn fewest_moves_cycle()
+    {
+        let following = HashMap::from([(1, vec![2]), (2, vec![1])]);
+        assert!(fewest_moves(&[1, 2], &following).is_err());
+    }
+}